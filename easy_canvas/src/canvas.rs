@@ -1,10 +1,17 @@
 use color::Color;
-use style::Style;
-use shape::{ToDrawCmd, DrawCmd};
+use style::{Style, Stroke};
+use composite::CompositeOp;
+use shape::{ToDrawCmd, DrawCmd, Text, text};
+use font::{Font, TextMetrics};
+use layout::TextOptions;
 use transform::Transform;
-use host::Host;
+use host::{Host, PresentMode};
+use draw_list::DrawList;
+use snapshot::SnapshotReply;
 
-use std::sync::mpsc::Sender;
+use std::io;
+use std::sync::mpsc::{Sender, Receiver};
+use std::path::Path;
 
 /// A generic drawing target.
 pub trait Target {
@@ -22,10 +29,78 @@ pub trait Target {
 
     /// Execute `cb` where each draw call has an implicit style and no implict transform.
     fn with_style<F: FnMut(ImplicitTarget<Self>)>(&self, style: Style, cb: F);
+
+    /// Execute `cb` where everything drawn is restricted to the interior of `shape` under
+    /// `transform`. Draw calls have `transform` as their implicit transform.
+    ///
+    /// Nested clips intersect. The clip shape is filled with the non-zero rule; lines and other
+    /// shapes without an interior clip everything away. Clearing the canvas is never clipped.
+    fn with_clip<D: ToDrawCmd, F: FnMut(ImplicitTarget<Self>)>(&self, transform: Transform,
+        shape: D, cb: F);
+
+    /// Execute `cb` where everything drawn is combined with the canvas using `op`, and each draw
+    /// call has no implicit transform.
+    fn with_composite<F: FnMut(ImplicitTarget<Self>)>(&self, op: CompositeOp, cb: F);
+
+    /// Execute `cb` where the opacity of everything drawn is multiplied by `alpha`, and each draw
+    /// call has no implicit transform. Nested alphas multiply.
+    fn with_alpha<F: FnMut(ImplicitTarget<Self>)>(&self, alpha: f32, cb: F);
+
+    /// Execute `cb` where everything is drawn onto a separate, initially transparent layer, and
+    /// each draw call has no implicit transform. Once `cb` returns, the layer is drawn onto the
+    /// canvas as a whole with its opacity multiplied by `alpha`.
+    ///
+    /// Unlike `with_alpha`, overlapping shapes in the group do not show through each other. The
+    /// layer is drawn with the alpha, composite operation and clip the group is in; inside the
+    /// group, drawing starts over with full opacity and the default composite operation.
+    fn with_opacity_group<F: FnMut(ImplicitTarget<Self>)>(&self, alpha: f32, cb: F);
+
+    /// Execute `cb` where everything is drawn onto the layer numbered `layer`, and each draw call
+    /// has no implicit transform. Everything drawn outside of a layer scope is drawn onto layer
+    /// 0.
+    ///
+    /// Each layer is a transparent canvas of its own, which keeps what is drawn onto it until it
+    /// is cleared. The layers are composited in order of their number, so e.g. a background on
+    /// layer -1 shows up behind the world on layer 0 and a UI on layer 1, no matter the order
+    /// they are drawn in. Clearing only clears the layer it is drawn onto. Inside an opacity
+    /// group, everything is drawn onto the group regardless of layer scopes.
+    fn with_layer<F: FnMut(ImplicitTarget<Self>)>(&self, layer: i32, cb: F);
+
+    /// Draws every command recorded in `list` so far, with `transform` applied after each
+    /// command's own transform.
+    fn draw_list(&self, transform: Transform, list: &DrawList);
+
+    /// Draws a `text` shape filled with `style`'s paint.
+    ///
+    /// Stroke fonts such as `Font::hershey_simplex` have nothing to fill; use `stroke_text`.
+    fn fill_text(&self, transform: Transform, style: Style, text: Text) {
+        self.draw(transform, Style::fill(style.paint().clone()), text);
+    }
+
+    /// Draws the outline of a `text` shape with `style`'s paint.
+    ///
+    /// Uses `style`'s stroke if it has one and a one unit wide stroke otherwise.
+    fn stroke_text(&self, transform: Transform, style: Style, text: Text) {
+        let style = match style {
+            Style::StrokeStyle { .. } => style,
+            Style::FillStyle { paint, .. } => Style::stroke_with(paint, Stroke::default()),
+        };
+        self.draw(transform, style, text);
+    }
+
+    /// Identical to Font::measure_text.
+    fn measure_text(&self, font: &Font, content: &str, size: f32) -> TextMetrics {
+        font.measure_text(content, size)
+    }
 }
 
 /// Wraps a parent Target and pairs it with an implict transform and style.
 ///
+/// Implicit targets can open nested scopes of their own. Like saving and restoring the state of
+/// an HTML5 Canvas, a nested scope's transform applies on top of its parent's, so shapes can be
+/// drawn relative to the shape they hang off of. The `override` variants ignore the parent's
+/// transform instead.
+///
 /// See [Target](#trait.Target).
 pub struct ImplicitTarget<'p, T: Target + 'p> {
     parent: &'p T,
@@ -43,15 +118,122 @@ impl<'p, T: Target> ImplicitTarget<'p, T> {
 
 impl<'p, T: Target> ImplicitTarget<'p, T> {
     pub fn draw<D: ToDrawCmd>(&self, shape: D) {
-        self.parent.draw(self.transform, self.style, shape);
+        self.parent.draw(self.transform, self.style.clone(), shape);
+    }
+
+    /// Draws every command recorded in `list` so far, with the implicit transform applied after
+    /// each command's own transform.
+    pub fn draw_list(&self, list: &DrawList) {
+        self.parent.draw_list(self.transform, list);
     }
 
     pub fn draw_with_style<D: ToDrawCmd>(&self, style: Style, shape: D) {
         self.parent.draw(self.transform, style, shape);
     }
 
+    /// Draws `shape` with `transform` applied before the implicit transform.
     pub fn draw_with_transform<D: ToDrawCmd>(&self, transform: Transform, shape: D) {
-        self.parent.draw(transform, self.style, shape);
+        self.parent.draw(transform.then(self.transform), self.style.clone(), shape);
+    }
+
+    /// Draws `shape` with `transform` in place of the implicit transform.
+    pub fn draw_with_override_transform<D: ToDrawCmd>(&self, transform: Transform, shape: D) {
+        self.parent.draw(transform, self.style.clone(), shape);
+    }
+
+    /// Execute `cb` in a nested scope with `transform` applied before the implicit transform, and
+    /// `style` in place of the implicit style.
+    pub fn with_state<F: FnMut(ImplicitTarget<T>)>(&self, transform: Transform, style: Style,
+            mut cb: F) {
+        cb(ImplicitTarget::from_state(self.parent, transform.then(self.transform), style));
+    }
+
+    /// Execute `cb` in a nested scope with `transform` applied before the implicit transform.
+    ///
+    /// Unlike `Target::with_transform`, the nested scope keeps the implicit style.
+    pub fn with_transform<F: FnMut(ImplicitTarget<T>)>(&self, transform: Transform, cb: F) {
+        self.with_state(transform, self.style.clone(), cb);
+    }
+
+    /// Execute `cb` in a nested scope with `style` in place of the implicit style.
+    ///
+    /// Unlike `Target::with_style`, the nested scope keeps the implicit transform.
+    pub fn with_style<F: FnMut(ImplicitTarget<T>)>(&self, style: Style, cb: F) {
+        self.with_state(Transform::identity(), style, cb);
+    }
+
+    /// Execute `cb` in a nested scope where everything drawn is restricted to the interior of
+    /// `shape` under the implicit transform, intersected with any clip the scope is already in.
+    pub fn with_clip<D: ToDrawCmd, F: FnMut(ImplicitTarget<T>)>(&self, shape: D, mut cb: F) {
+        self.parent.with_clip(self.transform, shape, |_| {
+            cb(ImplicitTarget::from_state(self.parent, self.transform, self.style.clone()));
+        });
+    }
+
+    /// Execute `cb` in a nested scope where everything drawn is combined with the canvas using
+    /// `op`.
+    pub fn with_composite<F: FnMut(ImplicitTarget<T>)>(&self, op: CompositeOp, mut cb: F) {
+        self.parent.with_composite(op, |_| {
+            cb(ImplicitTarget::from_state(self.parent, self.transform, self.style.clone()));
+        });
+    }
+
+    /// Execute `cb` in a nested scope where the opacity of everything drawn is multiplied by
+    /// `alpha`.
+    pub fn with_alpha<F: FnMut(ImplicitTarget<T>)>(&self, alpha: f32, mut cb: F) {
+        self.parent.with_alpha(alpha, |_| {
+            cb(ImplicitTarget::from_state(self.parent, self.transform, self.style.clone()));
+        });
+    }
+
+    /// Execute `cb` in a nested scope that is drawn onto the canvas as a whole with its opacity
+    /// multiplied by `alpha`. See `Target::with_opacity_group`.
+    pub fn with_opacity_group<F: FnMut(ImplicitTarget<T>)>(&self, alpha: f32, mut cb: F) {
+        self.parent.with_opacity_group(alpha, |_| {
+            cb(ImplicitTarget::from_state(self.parent, self.transform, self.style.clone()));
+        });
+    }
+
+    /// Execute `cb` in a nested scope where everything is drawn onto the layer numbered `layer`.
+    /// See `Target::with_layer`.
+    pub fn with_layer<F: FnMut(ImplicitTarget<T>)>(&self, layer: i32, mut cb: F) {
+        self.parent.with_layer(layer, |_| {
+            cb(ImplicitTarget::from_state(self.parent, self.transform, self.style.clone()));
+        });
+    }
+
+    /// Draws `shape` combined with the canvas using `op`.
+    pub fn draw_with_composite<D: ToDrawCmd>(&self, op: CompositeOp, shape: D) {
+        // the scope's callback may be called more than once as far as the type system knows
+        let mut shape = Some(shape);
+        self.with_composite(op, |t| if let Some(shape) = shape.take() {
+            t.draw(shape);
+        });
+    }
+
+    /// Execute `cb` in a nested scope with `transform` and `style` in place of the implicit ones,
+    /// as if the scope was opened on the parent Target.
+    pub fn with_override_state<F: FnMut(ImplicitTarget<T>)>(&self, transform: Transform,
+            style: Style, mut cb: F) {
+        cb(ImplicitTarget::from_state(self.parent, transform, style));
+    }
+
+    /// Draws text filled with the implicit style's paint, with its first baseline starting at
+    /// `(x, y)`. Lines are only broken at newlines; draw a `text` shape for other `TextOptions`.
+    ///
+    /// Stroke fonts such as `Font::hershey_simplex` have nothing to fill; use `stroke_text`.
+    pub fn fill_text(&self, font: &Font, content: &str, x: f32, y: f32, size: f32) {
+        let text = text(font.clone(), content.to_string(), x, y, size, TextOptions::default());
+        self.parent.fill_text(self.transform, self.style.clone(), text);
+    }
+
+    /// Draws the outline of text with the implicit style's paint, with its first baseline starting
+    /// at `(x, y)`. Lines are only broken at newlines.
+    ///
+    /// Uses the implicit style's stroke if it has one and a one unit wide stroke otherwise.
+    pub fn stroke_text(&self, font: &Font, content: &str, x: f32, y: f32, size: f32) {
+        let text = text(font.clone(), content.to_string(), x, y, size, TextOptions::default());
+        self.parent.stroke_text(self.transform, self.style.clone(), text);
     }
 
     /// Identical to Target::clear.
@@ -94,6 +276,53 @@ impl Target for Canvas {
     fn with_transform<F: FnMut(ImplicitTarget<Self>)>(&self, transform: Transform, cb: F) {
         self.with_state(transform, Style::fill((0, 0, 0)), cb);
     }
+
+    fn with_clip<D: ToDrawCmd, F: FnMut(ImplicitTarget<Self>)>(&self, transform: Transform,
+            shape: D, cb: F) {
+        let clip = shape.with_state(transform, Style::fill((0, 0, 0)));
+        self.tx.send(DrawCmd::PushClip(Box::new(clip)))
+            .expect("Canvas host hung up");
+        self.with_transform(transform, cb);
+        self.tx.send(DrawCmd::PopClip {})
+            .expect("Canvas host hung up");
+    }
+
+    fn with_composite<F: FnMut(ImplicitTarget<Self>)>(&self, op: CompositeOp, cb: F) {
+        self.tx.send(DrawCmd::PushComposite(op))
+            .expect("Canvas host hung up");
+        self.with_transform(Transform::identity(), cb);
+        self.tx.send(DrawCmd::PopComposite {})
+            .expect("Canvas host hung up");
+    }
+
+    fn with_alpha<F: FnMut(ImplicitTarget<Self>)>(&self, alpha: f32, cb: F) {
+        self.tx.send(DrawCmd::PushAlpha(alpha))
+            .expect("Canvas host hung up");
+        self.with_transform(Transform::identity(), cb);
+        self.tx.send(DrawCmd::PopAlpha {})
+            .expect("Canvas host hung up");
+    }
+
+    fn with_opacity_group<F: FnMut(ImplicitTarget<Self>)>(&self, alpha: f32, cb: F) {
+        self.tx.send(DrawCmd::PushGroup(alpha))
+            .expect("Canvas host hung up");
+        self.with_transform(Transform::identity(), cb);
+        self.tx.send(DrawCmd::PopGroup {})
+            .expect("Canvas host hung up");
+    }
+
+    fn with_layer<F: FnMut(ImplicitTarget<Self>)>(&self, layer: i32, cb: F) {
+        self.tx.send(DrawCmd::PushLayer(layer))
+            .expect("Canvas host hung up");
+        self.with_transform(Transform::identity(), cb);
+        self.tx.send(DrawCmd::PopLayer {})
+            .expect("Canvas host hung up");
+    }
+
+    fn draw_list(&self, transform: Transform, list: &DrawList) {
+        self.tx.send(DrawCmd::List(list.recording(), transform))
+            .expect("Canvas host hung up");
+    }
 }
 
 impl Canvas {
@@ -108,6 +337,47 @@ impl Canvas {
             .expect("Canvas host hung up");
     }
 
+    /// Mark the end of a frame, so the host shows everything drawn so far at once.
+    ///
+    /// Until a canvas presents its first frame or sets a `PresentMode`, hosts show drawing as it
+    /// arrives. Afterwards, they only show complete frames, so animations do not flicker or
+    /// show frames that are halfway drawn. Hosts that do not show the canvas ignore this.
+    pub fn present(&self) {
+        self.tx.send(DrawCmd::Present {})
+            .expect("Canvas host hung up");
+    }
+
+    /// Choose when the host shows what has been drawn, e.g. `PresentMode::Immediate` to keep
+    /// showing drawing as it arrives even though `present` is called.
+    pub fn set_present_mode(&self, mode: PresentMode) {
+        self.tx.send(DrawCmd::SetPresentMode(mode))
+            .expect("Canvas host hung up");
+    }
+
+    /// Collect everything drawn by the callback and send it to the host in a single message.
+    ///
+    /// Scenes made of many small shapes draw faster this way, since the host receives them all
+    /// at once instead of picking up one command per shape. The host draws nothing of the batch
+    /// until the callback returns.
+    pub fn batch<F: FnMut(&DrawList)>(&self, mut cb: F) {
+        let list = DrawList::new();
+        cb(&list);
+        self.tx.send(DrawCmd::Batch(list.into_commands()))
+            .expect("Canvas host hung up");
+    }
+
+    /// Save the canvas, as it looks once every earlier command has been drawn, to a PNG file.
+    ///
+    /// The host writes the file; post process shaders are not applied to the snapshot. The
+    /// returned channel receives whether the file could be written once the host is done, so
+    /// receiving from it also waits for the snapshot.
+    pub fn snapshot<P: AsRef<Path>>(&self, path: P) -> Receiver<io::Result<()>> {
+        let (reply, rx) = SnapshotReply::new();
+        self.tx.send(DrawCmd::Capture(path.as_ref().to_path_buf(), reply))
+            .expect("Canvas host hung up");
+        rx
+    }
+
     pub fn stop(self) {
         self.tx.send(DrawCmd::Stop {})
             .expect("Canvas host hung up");
@@ -37,6 +37,12 @@ impl Color for (f32, f32, f32) {
     }
 }
 
+/// Converts a normalized color to RGBA8, clamping each component.
+pub fn to_bytes(color: Rgba) -> [u8; 4] {
+    let to_byte = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [to_byte(color.0), to_byte(color.1), to_byte(color.2), to_byte(color.3)]
+}
+
 fn btwn(v: f32, a: f32, b: f32) -> bool {
     v <= a && v >= b
 }
\ No newline at end of file
@@ -0,0 +1,67 @@
+//! How drawn shapes are combined with what is already on the canvas, like
+//! `globalCompositeOperation` in HTML5.
+
+/// How the colors of a shape (the source) are combined with the canvas (the destination).
+///
+/// Unlike HTML5 Canvas, pixels outside of a shape are never changed, so e.g. `SourceIn` does not
+/// erase the rest of the canvas. Blend modes that need more than a weighted sum of the source
+/// and destination, like overlay or darken, are not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompositeOp {
+    /// The shape is drawn on top of the canvas. This is the default.
+    #[default]
+    SourceOver,
+    /// The shape is only drawn where the canvas is opaque, replacing it.
+    SourceIn,
+    /// The shape is only drawn where the canvas is transparent.
+    SourceOut,
+    /// The shape is only drawn where the canvas is opaque, on top of it.
+    SourceAtop,
+    /// The shape is drawn behind the canvas.
+    DestinationOver,
+    /// The canvas is kept where the shape is opaque and erased elsewhere inside the shape.
+    DestinationIn,
+    /// The canvas is erased where the shape is opaque, like an eraser.
+    DestinationOut,
+    /// The canvas is kept where the shape is opaque and drawn on top of the shape.
+    DestinationAtop,
+    /// The shape's colors are added to the canvas, which suits glows and particles.
+    Lighter,
+    /// The shape replaces the canvas.
+    Copy,
+    /// The shape and canvas are kept where the other is transparent.
+    Xor,
+    /// The shape's colors are multiplied with the canvas, which darkens it. Where either is
+    /// transparent the other is kept, like in HTML5.
+    Multiply,
+    /// The inverted colors of the shape and canvas are multiplied, which lightens the canvas.
+    Screen,
+}
+
+impl CompositeOp {
+    /// The factors the source and destination of `channel` are weighted with, given premultiplied
+    /// source and destination colors. Mirrors the blend functions of the OpenGL backend.
+    ///
+    /// Multiplying is `Cs·Cd + Cs·(1 - αd) + Cd·(1 - αs)`, which is weighted as
+    /// `Cs·(Cd + 1 - αd) + Cd·(1 - αs)`. No single blend function can do that, so the OpenGL
+    /// backend blends it in two passes.
+    pub(crate) fn factors(self, source: [f32; 4], destination: [f32; 4], channel: usize)
+            -> (f32, f32) {
+        let (sa, da) = (source[3], destination[3]);
+        match self {
+            CompositeOp::SourceOver => (1.0, 1.0 - sa),
+            CompositeOp::SourceIn => (da, 0.0),
+            CompositeOp::SourceOut => (1.0 - da, 0.0),
+            CompositeOp::SourceAtop => (da, 1.0 - sa),
+            CompositeOp::DestinationOver => (1.0 - da, 1.0),
+            CompositeOp::DestinationIn => (0.0, sa),
+            CompositeOp::DestinationOut => (0.0, 1.0 - sa),
+            CompositeOp::DestinationAtop => (1.0 - da, sa),
+            CompositeOp::Lighter => (1.0, 1.0),
+            CompositeOp::Copy => (1.0, 0.0),
+            CompositeOp::Xor => (1.0 - da, 1.0 - sa),
+            CompositeOp::Multiply => (destination[channel] + 1.0 - da, 1.0 - sa),
+            CompositeOp::Screen => (1.0, 1.0 - source[channel]),
+        }
+    }
+}
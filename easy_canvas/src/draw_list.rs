@@ -0,0 +1,194 @@
+//! Commands that are recorded once and drawn any number of times.
+
+use shape::{DrawCmd, ToDrawCmd};
+use style::Style;
+use transform::Transform;
+use color::Color;
+use composite::CompositeOp;
+use canvas::{Target, ImplicitTarget};
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A `Target` that records the commands drawn onto it instead of sending them to a host.
+///
+/// Drawing the list onto a canvas with `Target::draw_list` sends every recorded command in a
+/// single message, optionally moved by an extra transform. Hosts can tell a list they have seen
+/// before apart from a new one, so e.g. the `drawing` module tessellates static scenery or a
+/// widget drawn in many places only once.
+pub struct DrawList {
+    recording: RefCell<Recording>,
+}
+
+/// The commands recorded by a `DrawList` at some point, as sent to hosts.
+///
+/// Cloning a recording is cheap; clones share the same commands. Like textures, the commands
+/// cannot change, and recording more commands into the list creates a new recording.
+#[derive(Clone)]
+pub struct Recording {
+    data: Arc<RecordingData>,
+}
+
+#[derive(Clone)]
+struct RecordingData {
+    id: usize,
+    commands: Vec<DrawCmd>,
+}
+
+impl Recording {
+    fn new() -> Recording {
+        Recording {
+            data: Arc::new(RecordingData {
+                id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                commands: Vec::new(),
+            }),
+        }
+    }
+
+    /// A number that no other recording created by this process shares.
+    pub fn id(&self) -> usize {
+        self.data.id
+    }
+
+    /// The recorded commands, oldest first.
+    pub fn commands(&self) -> &[DrawCmd] {
+        &self.data.commands
+    }
+
+    /// Whether this is the only handle to the commands.
+    ///
+    /// Hosts that cache recordings use this to find recordings nobody can draw anymore.
+    #[cfg(feature = "drawing")]
+    pub(crate) fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.data) == 1
+    }
+}
+
+impl fmt::Debug for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Recording")
+            .field("id", &self.data.id)
+            .field("commands", &self.data.commands.len())
+            .finish()
+    }
+}
+
+/// Recordings are equal if they share the same commands, i.e. one is a clone of the other.
+impl PartialEq for Recording {
+    fn eq(&self, other: &Recording) -> bool {
+        self.data.id == other.data.id
+    }
+}
+
+impl DrawList {
+    pub fn new() -> DrawList {
+        DrawList {
+            recording: RefCell::new(Recording::new()),
+        }
+    }
+
+    /// The commands recorded so far. Recording more commands does not change the result.
+    pub fn recording(&self) -> Recording {
+        self.recording.borrow().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.recording.borrow().commands().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The commands recorded so far, oldest first. Only copies them if a recording of them is
+    /// still around.
+    pub fn into_commands(self) -> Vec<DrawCmd> {
+        match Arc::try_unwrap(self.recording.into_inner().data) {
+            Ok(data) => data.commands,
+            Err(data) => data.commands.clone(),
+        }
+    }
+
+    /// Removes every recorded command, so the list can be recorded again from scratch.
+    pub fn reset(&self) {
+        *self.recording.borrow_mut() = Recording::new();
+    }
+
+    fn record(&self, cmd: DrawCmd) {
+        let mut recording = self.recording.borrow_mut();
+        // hosts may still hold the old recording, which has to keep its commands and its id
+        let data = Arc::make_mut(&mut recording.data);
+        data.id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        data.commands.push(cmd);
+    }
+}
+
+impl Default for DrawList {
+    fn default() -> DrawList {
+        DrawList::new()
+    }
+}
+
+impl Target for DrawList {
+    fn draw<D: ToDrawCmd>(&self, transform: Transform, style: Style, shape: D) {
+        self.record(shape.with_state(transform, style));
+    }
+
+    fn clear<C: Color>(&self, color: C) {
+        self.record(DrawCmd::Clear(color.normalize()));
+    }
+
+    fn with_state<F: FnMut(ImplicitTarget<Self>)>(&self, transform: Transform,
+            style: Style, mut cb: F) {
+        cb(ImplicitTarget::from_state(self, transform, style));
+    }
+
+    fn with_style<F: FnMut(ImplicitTarget<Self>)>(&self, style: Style, cb: F) {
+        self.with_state(Transform::identity(), style, cb);
+    }
+
+    fn with_transform<F: FnMut(ImplicitTarget<Self>)>(&self, transform: Transform, cb: F) {
+        self.with_state(transform, Style::fill((0, 0, 0)), cb);
+    }
+
+    fn with_clip<D: ToDrawCmd, F: FnMut(ImplicitTarget<Self>)>(&self, transform: Transform,
+            shape: D, cb: F) {
+        let clip = shape.with_state(transform, Style::fill((0, 0, 0)));
+        self.record(DrawCmd::PushClip(Box::new(clip)));
+        self.with_transform(transform, cb);
+        self.record(DrawCmd::PopClip {});
+    }
+
+    fn with_composite<F: FnMut(ImplicitTarget<Self>)>(&self, op: CompositeOp, cb: F) {
+        self.record(DrawCmd::PushComposite(op));
+        self.with_transform(Transform::identity(), cb);
+        self.record(DrawCmd::PopComposite {});
+    }
+
+    fn with_alpha<F: FnMut(ImplicitTarget<Self>)>(&self, alpha: f32, cb: F) {
+        self.record(DrawCmd::PushAlpha(alpha));
+        self.with_transform(Transform::identity(), cb);
+        self.record(DrawCmd::PopAlpha {});
+    }
+
+    fn with_opacity_group<F: FnMut(ImplicitTarget<Self>)>(&self, alpha: f32, cb: F) {
+        self.record(DrawCmd::PushGroup(alpha));
+        self.with_transform(Transform::identity(), cb);
+        self.record(DrawCmd::PopGroup {});
+    }
+
+    fn with_layer<F: FnMut(ImplicitTarget<Self>)>(&self, layer: i32, cb: F) {
+        self.record(DrawCmd::PushLayer(layer));
+        self.with_transform(Transform::identity(), cb);
+        self.record(DrawCmd::PopLayer {});
+    }
+
+    fn draw_list(&self, transform: Transform, list: &DrawList) {
+        let recording = list.recording();
+        self.record(DrawCmd::List(recording, transform));
+    }
+}
@@ -2,25 +2,44 @@
 //!
 //! Needs a significant overhaul and has a lot of areas that can be improved for performance.
 
-use shape::DrawCmd;
-use color::Rgba;
-
-use cgmath::Matrix4;
+use shape::{DrawCmd, Image};
+use texture::Texture;
+use transform::Transform;
+use color::{self, Rgba};
+use tessellate::{self, Outline, Tessellation};
+use draw_list::Recording;
+use paint::{Paint, Gradient, GradientKind};
+use style::{Style, FillRule};
+use composite::CompositeOp;
+use host::PresentMode;
+use snapshot;
+
+use cgmath::{Matrix, Matrix4, SquareMatrix, Point2};
 
 use gl;
 use gl::types::*;
 
+use rusttype::gpu_cache::Cache;
+
 use std::sync::mpsc::Receiver;
+use std::collections::{HashMap, BTreeMap};
 use std::ptr;
 use std::os::raw::c_void;
-use std::mem::size_of_val;
+use std::mem::{size_of, size_of_val};
 use std::i16;
 
+/// Stencil bit that marks pixels outside of the clip. Nothing is drawn to marked pixels.
+const CLIPPED_BIT: GLuint = 0x80;
+
+/// Stencil bits that count how often an outline winds around each pixel while it is filled.
+/// They are zero between draws.
+const WINDING_BITS: GLuint = 0x7F;
+
 /// Default Vertex Shader used for rendering objects to a canvas layer
 static VERTEX_SHADER: &'static str = r#"
     #version 330 core
 
-    // uniform mat4 proj_matrix;
+    uniform mat4 proj_matrix;
     // uniform mat4 cam_matrix;
 
     layout (location = 0) in vec3 pos;
@@ -29,12 +48,14 @@ static VERTEX_SHADER: &'static str = r#"
 
     out vec4 pass_color;
     out vec2 pass_uv;
+    out vec2 pass_pos;
 
     void main(void) {
-        gl_Position = vec4(pos, 1.0);
+        gl_Position = proj_matrix * vec4(pos, 1.0);
 
         pass_color = color;
         pass_uv = uv;
+        pass_pos = pos.xy;
     }
 "#;
 
@@ -44,16 +65,98 @@ static FRAGMENT_SHADER: &'static str = r#"
 
     in vec4 pass_color;
     in vec2 pass_uv;
+    in vec2 pass_pos;
 
     out vec4 FragColor;
 
     uniform sampler2D diffuse;
 
+    // 0: flat color, 1: linear, 2: radial, 3: conic gradient
+    uniform int paint_kind;
+    // maps canvas coordinates to the gradient's coordinates
+    uniform mat3 paint_matrix;
+    uniform vec4 paint_params[2];
+    // the gradient's colors, sampled at 256 evenly spaced offsets
+    uniform sampler2D ramp;
+    // shapes are blended with premultiplied alpha; the flat target already is premultiplied
+    uniform bool premultiply;
+    // the product of the alphas in effect, applied to premultiplied colors
+    uniform float alpha;
+
+    const float PI = 3.14159265358979;
+
+    // Mirrors `radial_param` in paint.rs. Returns false where the gradient does not cover the
+    // point.
+    bool radial_param(vec2 p, float r0, vec2 c, float dr, out float t) {
+        float a = dot(c, c) - dr * dr;
+        float b = dot(p, c) + r0 * dr;
+        float cc = dot(p, p) - r0 * r0;
+        if (abs(a) <= 1e-6) {
+            t = b == 0.0 ? 0.0 : cc / (2.0 * b);
+            return b != 0.0 && r0 + t * dr >= 0.0;
+        }
+        float disc = b * b - a * cc;
+        if (disc < 0.0) {
+            return false;
+        }
+        float root = sqrt(disc);
+        float t0 = (b + root) / a;
+        float t1 = (b - root) / a;
+        t = max(t0, t1);
+        if (r0 + t * dr >= 0.0) {
+            return true;
+        }
+        t = min(t0, t1);
+        return r0 + t * dr >= 0.0;
+    }
+
+    vec4 paint_color() {
+        if (paint_kind == 0) {
+            return vec4(1.0);
+        }
+        vec2 p = (paint_matrix * vec3(pass_pos, 1.0)).xy;
+        float t;
+        if (paint_kind == 1) {
+            vec2 start = paint_params[0].xy;
+            vec2 dir = paint_params[0].zw - start;
+            float len2 = dot(dir, dir);
+            if (len2 == 0.0) {
+                return vec4(0.0);
+            }
+            t = dot(p - start, dir) / len2;
+        } else if (paint_kind == 2) {
+            vec3 start = paint_params[0].xyz;
+            vec3 end = paint_params[1].xyz;
+            if (!radial_param(p - start.xy, start.z, end.xy - start.xy, end.z - start.z, t)) {
+                return vec4(0.0);
+            }
+        } else {
+            float angle = atan(p.y - paint_params[0].z, p.x - paint_params[0].y)
+                - paint_params[0].x;
+            t = fract(angle / (2.0 * PI));
+        }
+        return texture(ramp, vec2(clamp(t, 0.0, 1.0) * 255.0 / 256.0 + 0.5 / 256.0, 0.5));
+    }
+
     void main(void) {
-        FragColor = texture(diffuse, pass_uv) * pass_color;
+        vec4 color = texture(diffuse, pass_uv) * pass_color * paint_color();
+        FragColor = (premultiply ? vec4(color.rgb * color.a, color.a) : color) * alpha;
     }
 "#;
 
+/// Number of colors sampled from a gradient into the ramp texture
+const RAMP_SIZE: usize = 256;
+
+/// Width and height of the texture glyphs are rasterized into
+const GLYPH_ATLAS_SIZE: u32 = 1024;
+
+/// Text with a larger font size on the screen, in pixels per em, is tessellated instead of being
+/// drawn from the glyph atlas
+const MAX_ATLAS_FONT_SIZE: f32 = 128.0;
+
+/// Number of vertices a batch holds at most before it is drawn
+const MAX_BATCH_VERTICES: usize = 1 << 16;
+
 /// Framebuffer object
 pub type Fbo = GLuint;
 pub type Tex = GLuint;
@@ -74,14 +177,90 @@ pub struct GlRenderTarget {
     fbo: Fbo,
     tex: Tex,
     diffuse_loc: GLint,
+    proj_loc: GLint,
     screen_program: GlProgram,
     post_process_program: Option<GlProgram>,
     post_process_res_loc: GLint,
     screen_quad_vao: Vao,
     screen_quad_vbo: BuffObj,
+    shape_vao: Vao,
+    shape_vbo: BuffObj,
+    /// A 1x1 white texture bound while drawing untextured shapes
+    white_tex: Tex,
+    paint_kind_loc: GLint,
+    paint_matrix_loc: GLint,
+    paint_params_loc: GLint,
+    premultiply_loc: GLint,
+    alpha_loc: GLint,
+    /// A 256x1 texture holding the colors of the gradient being drawn
+    ramp_tex: Tex,
+    /// Uploaded image textures by texture id, along with a handle that keeps the id from being
+    /// reused while the GL texture exists
+    textures: HashMap<usize, (Texture, Tex)>,
+    /// Keeps track of which glyphs are in the glyph atlas
+    glyph_cache: Cache<'static>,
+    /// Single channel glyph coverage, read as white with the coverage in alpha
+    glyph_tex: Tex,
+    /// The outlines of the clips in effect, innermost last
+    clips: Vec<Outline>,
+    /// The composite operations in effect, innermost last
+    composites: Vec<CompositeOp>,
+    /// The product of every alpha in effect, innermost last
+    alphas: Vec<f32>,
+    /// The alphas of the open opacity groups, innermost last
+    groups: Vec<f32>,
+    /// Offscreen layers for opacity groups, one per nesting depth. They are kept around once
+    /// created so groups drawn every frame do not allocate.
+    layers: Vec<Layer>,
+    /// The indices of the canvas layers being drawn onto, innermost last. Without any, drawing
+    /// goes to layer 0.
+    layer_stack: Vec<i32>,
+    /// Every canvas layer other than layer 0, which is drawn onto the target's own FBO
+    canvas_layers: BTreeMap<i32, Layer>,
+    /// Vertices of consecutive draws that have not been drawn yet; see `add_to_batch`
+    batch: Vec<Vertex>,
+    /// The texture and texture filter the batched vertices are drawn with
+    batch_texture: (Tex, GLint),
+    /// Tessellated draw lists by recording id
+    lists: HashMap<usize, CachedList>,
+    /// When the flat FBO is updated, or None until the canvas presents a frame or chooses a mode
+    present_mode: Option<PresentMode>,
+    /// Whether the flat FBO holds a complete frame that has not been shown yet
+    frame_ready: bool,
+    /// A flat FBO that captures are composited into, so they never replace a presented frame.
+    /// Created on the first capture.
+    capture_target: Option<(Fbo, Tex)>,
+}
+
+/// A draw list's shapes, tessellated with the transform the list was last drawn with.
+///
+/// Shapes only have to be tessellated again if the list is drawn rotated, scaled or skewed
+/// differently; a different translation just moves the outlines.
+struct CachedList {
+    /// Keeps the id from being reused while the list is cached
+    recording: Recording,
+    transform: Transform,
+    cmds: Vec<CachedCmd>,
+}
+
+enum CachedCmd {
+    Shape(Tessellation),
+    /// Anything that is not drawn by filling an outline, without the list's transform applied
+    Other(DrawCmd),
+}
+
+/// A multi-sampled offscreen layer, and the flat texture it is resolved into before it is drawn
+/// onto another framebuffer.
+struct Layer {
+    ms_fbo: Fbo,
+    ms_tex: Tex,
+    ms_rbo: Rbo,
+    fbo: Fbo,
+    tex: Tex,
 }
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct Vertex([f32; 3], [u8; 4], [i16; 2]);
 
 #[allow(non_camel_case_types)]
@@ -249,9 +428,12 @@ fn declare_format() {
     }
 }
 
-fn make_projection_matrix(width: u32, height: u32, _layers: u32) -> Matrix4<f32> {
-    // TODO: Add support for rendering at discrete depths
-    assert_ne!(_layers, 0, "Cannot create a canvas with 0 layers");
+/// Maps canvas coordinates, which have the origin at the center and the y-axis pointing up, to
+/// normalized device coordinates.
+///
+/// Canvas layers are separate framebuffers that are composited in order, rather than depths, since
+/// the depth test cannot order shapes that are blended.
+fn make_projection_matrix(width: u32, height: u32) -> Matrix4<f32> {
     let (width, height) = (width as f32, height as f32);
     Matrix4::new(
         2.0 / width, 0.0, 0.0, 0.0,
@@ -326,6 +508,59 @@ fn create_color_render_target(width: i32, height: i32) -> (Fbo, Tex) {
     }
 }
 
+/// Create a 1x1 opaque white texture.
+fn create_white_texture() -> Tex {
+    unsafe {
+        let mut tex = 0;
+        gl::GenTextures(1, &mut tex);
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        let pixel = [255u8; 4];
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, 1, 1, 0, gl::RGBA,
+            gl::UNSIGNED_BYTE, pixel.as_ptr() as *const c_void);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        tex
+    }
+}
+
+/// Create a 256x1 texture for gradient color ramps. Its contents are uploaded per shape.
+fn create_ramp_texture() -> Tex {
+    unsafe {
+        let mut tex = 0;
+        gl::GenTextures(1, &mut tex);
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, RAMP_SIZE as i32, 1, 0, gl::RGBA,
+            gl::UNSIGNED_BYTE, ptr::null());
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        tex
+    }
+}
+
+/// Create the glyph atlas texture. Its contents are uploaded as glyphs are needed.
+fn create_glyph_texture() -> Tex {
+    unsafe {
+        let mut tex = 0;
+        gl::GenTextures(1, &mut tex);
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::R8 as i32, GLYPH_ATLAS_SIZE as i32,
+            GLYPH_ATLAS_SIZE as i32, 0, gl::RED, gl::UNSIGNED_BYTE, ptr::null());
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        // sample as white with the coverage in alpha so the vertex color tints the glyphs
+        let swizzle = [gl::ONE as i32, gl::ONE as i32, gl::ONE as i32, gl::RED as i32];
+        gl::TexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        tex
+    }
+}
+
 /// Creates a Framebuffer with a 2d texture and depth/stencil renderbuffer attachments
 ///
 /// Note that the texture is unitialized so the result is undefined if you use the texture before
@@ -334,6 +569,10 @@ pub fn create_render_target(width: i32, height: i32) -> GlRenderTarget {
     let (ms_fbo, ms_tex, ms_rbo) = create_ms_cds_render_target(width, height);
     let (fbo, tex) = create_color_render_target(width, height);
     let (screen_quad_vao, screen_quad_vbo) = create_vao();
+    let (shape_vao, shape_vbo) = create_vao();
+    let white_tex = create_white_texture();
+    let ramp_tex = create_ramp_texture();
+    let glyph_tex = create_glyph_texture();
     let screen_program = {
         let vert = create_shader(gl::VERTEX_SHADER, VERTEX_SHADER);
         let frag = create_shader(gl::FRAGMENT_SHADER, FRAGMENT_SHADER);
@@ -343,11 +582,35 @@ pub fn create_render_target(width: i32, height: i32) -> GlRenderTarget {
         program
     };
     let diffuse_loc;
+    let proj_loc;
+    let paint_kind_loc;
+    let paint_matrix_loc;
+    let paint_params_loc;
+    let premultiply_loc;
+    let alpha_loc;
     unsafe {
         diffuse_loc = gl::GetUniformLocation(screen_program,
             b"diffuse\0".as_ptr() as *const _);
+        proj_loc = gl::GetUniformLocation(screen_program,
+            b"proj_matrix\0".as_ptr() as *const _);
+        paint_kind_loc = gl::GetUniformLocation(screen_program,
+            b"paint_kind\0".as_ptr() as *const _);
+        paint_matrix_loc = gl::GetUniformLocation(screen_program,
+            b"paint_matrix\0".as_ptr() as *const _);
+        paint_params_loc = gl::GetUniformLocation(screen_program,
+            b"paint_params\0".as_ptr() as *const _);
+        premultiply_loc = gl::GetUniformLocation(screen_program,
+            b"premultiply\0".as_ptr() as *const _);
+        alpha_loc = gl::GetUniformLocation(screen_program,
+            b"alpha\0".as_ptr() as *const _);
+        let ramp_loc = gl::GetUniformLocation(screen_program,
+            b"ramp\0".as_ptr() as *const _);
         gl::UseProgram(screen_program);
         gl::Uniform1i(diffuse_loc, 0);
+        gl::Uniform1i(ramp_loc, 1);
+        gl::Uniform1i(paint_kind_loc, 0);
+        gl::Uniform1i(premultiply_loc, 1);
+        gl::Uniform1f(alpha_loc, 1.0);
         gl::UseProgram(0);
 
         gl::BindBuffer(gl::ARRAY_BUFFER, screen_quad_vbo);
@@ -374,18 +637,46 @@ pub fn create_render_target(width: i32, height: i32) -> GlRenderTarget {
         fbo,
         tex,
         diffuse_loc,
+        proj_loc,
         post_process_program: None,
         post_process_res_loc: -1,
         screen_program,
         screen_quad_vao,
         screen_quad_vbo,
+        shape_vao,
+        shape_vbo,
+        white_tex,
+        paint_kind_loc,
+        paint_matrix_loc,
+        paint_params_loc,
+        premultiply_loc,
+        alpha_loc,
+        ramp_tex,
+        textures: HashMap::new(),
+        glyph_cache: Cache::builder()
+            .dimensions(GLYPH_ATLAS_SIZE, GLYPH_ATLAS_SIZE)
+            .build(),
+        glyph_tex,
+        clips: Vec::new(),
+        composites: Vec::new(),
+        alphas: Vec::new(),
+        groups: Vec::new(),
+        layers: Vec::new(),
+        layer_stack: Vec::new(),
+        canvas_layers: BTreeMap::new(),
+        batch: Vec::new(),
+        batch_texture: (white_tex, gl::NEAREST as GLint),
+        lists: HashMap::new(),
+        present_mode: None,
+        frame_ready: false,
+        capture_target: None,
     }
 }
 
 /// Create a new custom post processing shader.
 ///
 /// Calls a function mainImage(inout fragColor, in pass_uv), which is expected to be defined in
-/// the frag_shader argument.
+/// the frag_shader argument. The `diffuse` texture holds the canvas with premultiplied alpha.
 pub fn create_post_process_shader(frag_shader: &str) -> GlProgram {
     let mut frag_shader_source = String::new();
     frag_shader_source.push_str(r#"
@@ -427,11 +718,37 @@ pub fn delete_render_target(target: GlRenderTarget) {
             fbo,
             tex,
             diffuse_loc: _,
+            proj_loc: _,
             post_process_program,
             post_process_res_loc: _,
             screen_program,
             screen_quad_vao,
             screen_quad_vbo,
+            shape_vao,
+            shape_vbo,
+            white_tex,
+            paint_kind_loc: _,
+            paint_matrix_loc: _,
+            paint_params_loc: _,
+            premultiply_loc: _,
+            alpha_loc: _,
+            ramp_tex,
+            textures,
+            glyph_cache: _,
+            glyph_tex,
+            clips: _,
+            composites: _,
+            alphas: _,
+            groups: _,
+            layers,
+            layer_stack: _,
+            canvas_layers,
+            batch: _,
+            batch_texture: _,
+            lists: _,
+            present_mode: _,
+            frame_ready: _,
+            capture_target,
         } = target;
 
         gl::UseProgram(0);
@@ -442,17 +759,29 @@ pub fn delete_render_target(target: GlRenderTarget) {
         gl::DeleteProgram(screen_program);
 
         gl::BindVertexArray(0);
-        gl::DeleteVertexArrays(1, &screen_quad_vao);
+        gl::DeleteVertexArrays(2, &[screen_quad_vao, shape_vao] as *const _);
 
         gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-        gl::DeleteBuffers(1, &screen_quad_vbo);
+        gl::DeleteBuffers(2, &[screen_quad_vbo, shape_vbo] as *const _);
 
         gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
         gl::DeleteFramebuffers(2, &[ms_fbo, fbo] as *const _);
 
         gl::DeleteRenderbuffers(1, &ms_rbo);
 
-        gl::DeleteTextures(2, &[ms_tex, tex] as *const _);
+        gl::DeleteTextures(5, &[ms_tex, tex, white_tex, ramp_tex, glyph_tex] as *const _);
+        for (_, (_, image_tex)) in textures {
+            gl::DeleteTextures(1, &image_tex);
+        }
+        if let Some((capture_fbo, capture_tex)) = capture_target {
+            gl::DeleteFramebuffers(1, &capture_fbo);
+            gl::DeleteTextures(1, &capture_tex);
+        }
+        for layer in layers.into_iter().chain(canvas_layers.into_values()) {
+            gl::DeleteFramebuffers(2, &[layer.ms_fbo, layer.fbo] as *const _);
+            gl::DeleteRenderbuffers(1, &layer.ms_rbo);
+            gl::DeleteTextures(2, &[layer.ms_tex, layer.tex] as *const _);
+        }
     }
 }
 
@@ -462,19 +791,93 @@ pub fn use_ms_render_target(target: &GlRenderTarget) {
     }
 }
 
+/// Bind the multi-sampled FBO that is drawn to: the layer of the innermost open opacity group,
+/// or else the canvas layer being drawn onto.
+fn use_current_render_target(target: &GlRenderTarget) {
+    let fbo = match target.groups.len() {
+        0 => match current_layer(target) {
+            0 => target.ms_fbo,
+            index => target.canvas_layers[&index].ms_fbo,
+        },
+        depth => target.layers[depth - 1].ms_fbo,
+    };
+    unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, fbo) };
+}
+
 pub fn use_flat_render_target(target: &GlRenderTarget) {
     unsafe {
         gl::BindFramebuffer(gl::FRAMEBUFFER, target.fbo);
     }
 }
 
+/// Resolve the multi-sampled FBO into the flat FBO, compositing every canvas layer in order of
+/// its index.
 pub fn update_flat_target(target: &GlRenderTarget) {
+    composite_layers(target, target.fbo);
+}
+
+/// Resolve the multi-sampled FBO into the flat FBO `fbo`, compositing every canvas layer in
+/// order of its index.
+fn composite_layers(target: &GlRenderTarget, fbo: Fbo) {
     unsafe {
         gl::BindFramebuffer(gl::READ_FRAMEBUFFER, target.ms_fbo);
-        gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target.fbo);
+        gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, fbo);
         gl::BlitFramebuffer(0, 0, target.width, target.height, 0, 0, target.width, target.height,
             gl::COLOR_BUFFER_BIT, gl::NEAREST);
     }
+    if target.canvas_layers.is_empty() {
+        return;
+    }
+    // layer 0 is already in place, so the layers above it are drawn on top of it in ascending
+    // order and the layers below it are drawn behind it in descending order
+    let above = target.canvas_layers.range(1..);
+    let below = target.canvas_layers.range(..0).rev();
+    for (&index, layer) in above.chain(below) {
+        resolve_layer(target, layer);
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, fbo) };
+        let op = if index > 0 {
+            CompositeOp::SourceOver
+        } else {
+            CompositeOp::DestinationOver
+        };
+        draw_layer(target, layer, 1.0, op);
+    }
+    use_composite(target.composites.last().cloned().unwrap_or_default());
+}
+
+/// Read back the flat color texture as RGBA8 pixels, top row first.
+///
+/// Call `update_flat_target` first so the flat target reflects the latest drawing.
+pub fn read_flat_target(target: &GlRenderTarget) -> Vec<u8> {
+    read_pixels(target, target.fbo)
+}
+
+/// Read back the color texture of the flat FBO `fbo` as RGBA8 pixels, top row first.
+fn read_pixels(target: &GlRenderTarget, fbo: Fbo) -> Vec<u8> {
+    let row_len = target.width as usize * 4;
+    let mut pixels = vec![0u8; row_len * target.height as usize];
+    unsafe {
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(0, 0, target.width, target.height, gl::RGBA, gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut c_void);
+    }
+    // OpenGL returns the bottom row first
+    let mut flipped = Vec::with_capacity(pixels.len());
+    for row in pixels.chunks(row_len).rev() {
+        flipped.extend_from_slice(row);
+    }
+    // the target holds premultiplied colors
+    for pixel in flipped.chunks_mut(4) {
+        let alpha = pixel[3] as u32;
+        for c in &mut pixel[..3] {
+            // fully transparent pixels are left alone
+            if let Some(straight) = (*c as u32 * 255).checked_div(alpha) {
+                *c = straight.min(255) as u8;
+            }
+        }
+    }
+    flipped
 }
 
 pub fn use_default_target() {
@@ -483,69 +886,876 @@ pub fn use_default_target() {
     }
 }
 
+/// Clears the bound framebuffer, which holds premultiplied colors, to `color`.
 pub fn clear(color: Rgba) {
     unsafe {
         let (r, g, b, a) = color;
-        gl::ClearColor(r, g, b, a);
+        gl::ClearColor(r * a, g * a, b * a, a);
+        gl::StencilMask(0xFF);
         gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
     }
 }
+/// Enables blending premultiplied colors over the framebuffer.
 pub fn enable_blending() {
+    use_composite(CompositeOp::SourceOver);
+}
+
+/// Sets up blending premultiplied colors with the framebuffer using `op`.
+///
+/// Mirrors `CompositeOp::factors`, which the raster backend uses.
+fn use_composite(op: CompositeOp) {
+    let (src, dst) = match op {
+        CompositeOp::SourceOver => (gl::ONE, gl::ONE_MINUS_SRC_ALPHA),
+        CompositeOp::SourceIn => (gl::DST_ALPHA, gl::ZERO),
+        CompositeOp::SourceOut => (gl::ONE_MINUS_DST_ALPHA, gl::ZERO),
+        CompositeOp::SourceAtop => (gl::DST_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+        CompositeOp::DestinationOver => (gl::ONE_MINUS_DST_ALPHA, gl::ONE),
+        CompositeOp::DestinationIn => (gl::ZERO, gl::SRC_ALPHA),
+        CompositeOp::DestinationOut => (gl::ZERO, gl::ONE_MINUS_SRC_ALPHA),
+        CompositeOp::DestinationAtop => (gl::ONE_MINUS_DST_ALPHA, gl::SRC_ALPHA),
+        CompositeOp::Lighter => (gl::ONE, gl::ONE),
+        CompositeOp::Copy => (gl::ONE, gl::ZERO),
+        CompositeOp::Xor => (gl::ONE_MINUS_DST_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+        CompositeOp::Multiply => (gl::DST_COLOR, gl::ONE_MINUS_SRC_ALPHA),
+        CompositeOp::Screen => (gl::ONE, gl::ONE_MINUS_SRC_COLOR),
+    };
     unsafe {
         gl::Enable(gl::BLEND);
-        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        match op {
+            // the first of two passes, see `draw_multiplied`; the alpha is left alone so the
+            // second pass can still tell where the canvas was transparent
+            CompositeOp::Multiply => gl::BlendFuncSeparate(src, dst, gl::ZERO, gl::ONE),
+            _ => gl::BlendFunc(src, dst),
+        }
         gl::BlendEquation(gl::FUNC_ADD);
     }
 }
 
+/// Sets up the second pass of multiplying, which adds the source where the canvas was
+/// transparent and blends the alpha like source-over.
+fn use_multiply_second_pass() {
+    unsafe {
+        gl::BlendFuncSeparate(gl::ONE_MINUS_DST_ALPHA, gl::ONE, gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+    }
+}
+
+/// Runs `draw`, which draws a single shape, image or text.
+///
+/// Multiplying takes two passes that each have to cover a pixel exactly once. So while
+/// `Multiply` is in effect, the shape is drawn onto a transparent group layer instead, which
+/// `draw_layer` then multiplies onto the canvas with a single quad per pass.
+fn draw_multiplied<F: FnOnce(&mut GlRenderTarget)>(target: &mut GlRenderTarget, draw: F) {
+    if target.composites.last() != Some(&CompositeOp::Multiply) {
+        draw(target);
+        return;
+    }
+    flush_batch(target);
+    push_group(target, 1.0);
+    draw(target);
+    flush_batch(target);
+    pop_group(target);
+}
+
+/// Prepare the screen program for drawing shapes in canvas coordinates, leaving out clipped
+/// pixels.
+fn begin_shapes(target: &GlRenderTarget) {
+    let proj = make_projection_matrix(target.width as u32, target.height as u32);
+    unsafe {
+        gl::UseProgram(target.screen_program);
+        gl::UniformMatrix4fv(target.proj_loc, 1, gl::FALSE, proj.as_ptr());
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, target.white_tex);
+        gl::BindVertexArray(target.shape_vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, target.shape_vbo);
+        gl::Uniform1i(target.premultiply_loc, 1);
+        gl::Uniform1f(target.alpha_loc, current_alpha(target));
+        gl::Enable(gl::STENCIL_TEST);
+        use_clip_test();
+    }
+}
+
+fn end_shapes() {
+    unsafe {
+        gl::Disable(gl::STENCIL_TEST);
+        gl::StencilMask(0xFF);
+        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        gl::BindVertexArray(0);
+        gl::UseProgram(0);
+    }
+}
+
+/// Only draw to pixels that are not clipped, and leave the stencil unchanged.
+unsafe fn use_clip_test() {
+    gl::StencilMask(0);
+    gl::StencilFunc(gl::EQUAL, 0, CLIPPED_BIT);
+    gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+}
+
+/// Triangle fans that wind around the interior of each contour of an outline.
+fn outline_fans(outline: &Outline, color: [u8; 4]) -> Vec<Vertex> {
+    let mut vertices = Vec::new();
+    for contour in &outline.contours {
+        if contour.len() < 3 {
+            continue;
+        }
+        let first = contour[0];
+        for pair in contour[1..].windows(2) {
+            vertices.push(Vertex([first.x, first.y, 0.0], color, [0, 0]));
+            vertices.push(Vertex([pair[0].x, pair[0].y, 0.0], color, [0, 0]));
+            vertices.push(Vertex([pair[1].x, pair[1].y, 0.0], color, [0, 0]));
+        }
+    }
+    vertices
+}
+
+/// Two triangles covering the rectangle between `min` and `max`.
+fn quad(min: Point2<f32>, max: Point2<f32>, color: [u8; 4]) -> [Vertex; 6] {
+    let vertex = |x: f32, y: f32| Vertex([x, y, 0.0], color, [0, 0]);
+    [
+        vertex(min.x, min.y),
+        vertex(max.x, min.y),
+        vertex(max.x, max.y),
+        vertex(max.x, max.y),
+        vertex(min.x, max.y),
+        vertex(min.x, min.y),
+    ]
+}
+
+/// Count windings of the `count` fan vertices at the start of the bound buffer into the winding
+/// bits of unclipped pixels. Returns the winding bits that are non-zero inside the outline.
+///
+/// For the non-zero rule the fan increments the count for counter clockwise triangles and
+/// decrements it for clockwise ones; for the even-odd rule it flips the lowest bit.
+unsafe fn count_windings(fill_rule: FillRule, count: usize) -> GLuint {
+    gl::StencilMask(WINDING_BITS);
+    gl::StencilFunc(gl::EQUAL, 0, CLIPPED_BIT);
+    let interior_mask = match fill_rule {
+        FillRule::NonZero => {
+            gl::StencilOpSeparate(gl::FRONT, gl::KEEP, gl::KEEP, gl::INCR_WRAP);
+            gl::StencilOpSeparate(gl::BACK, gl::KEEP, gl::KEEP, gl::DECR_WRAP);
+            WINDING_BITS
+        }
+        FillRule::EvenOdd => {
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::INVERT);
+            0x01
+        }
+    };
+    gl::DrawArrays(gl::TRIANGLES, 0, count as GLsizei);
+    interior_mask
+}
+
+/// Fill an outline using the stencil buffer.
+///
+/// Each contour is drawn as a triangle fan that counts windings in the stencil. A quad covering
+/// the outline's bounds is then drawn wherever the stencil marks the interior, which also resets
+/// the stencil for the next shape. Since windings are only counted for unclipped pixels, the fill
+/// is clipped too.
+///
+/// Expects `begin_shapes` to have been called.
+fn fill_outline(outline: &Outline, color: Rgba) {
+    let (min, max) = match outline.bounds() {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    let color = color::to_bytes(color);
+    let mut vertices = outline_fans(outline, color);
+    let fan_len = vertices.len();
+    if fan_len == 0 {
+        return;
+    }
+    vertices.extend_from_slice(&quad(min, max, color));
+
+    unsafe {
+        gl::BufferData(gl::ARRAY_BUFFER, (vertices.len() * size_of::<Vertex>()) as isize,
+            vertices.as_ptr() as *const c_void, gl::STREAM_DRAW);
+
+        gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+        let interior_mask = count_windings(outline.fill_rule, fan_len);
+
+        gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+        gl::StencilFunc(gl::NOTEQUAL, 0, interior_mask);
+        gl::StencilOp(gl::ZERO, gl::ZERO, gl::ZERO);
+        gl::DrawArrays(gl::TRIANGLES, fan_len as GLsizei, 6);
+
+        use_clip_test();
+    }
+}
+
+/// A quad a little larger than the whole canvas, in canvas coordinates.
+fn canvas_quad(target: &GlRenderTarget) -> [Vertex; 6] {
+    let (x, y) = (target.width as f32 / 2.0 + 1.0, target.height as f32 / 2.0 + 1.0);
+    quad(Point2::new(-x, -y), Point2::new(x, y), [0, 0, 0, 0])
+}
+
+/// Intersect the clip with an outline by marking every pixel outside of it as clipped.
+fn apply_clip(target: &GlRenderTarget, outline: &Outline) {
+    let mut vertices = outline_fans(outline, [0, 0, 0, 0]);
+    let fan_len = vertices.len();
+    vertices.extend_from_slice(&canvas_quad(target));
+
+    begin_shapes(target);
+    unsafe {
+        gl::BufferData(gl::ARRAY_BUFFER, (vertices.len() * size_of::<Vertex>()) as isize,
+            vertices.as_ptr() as *const c_void, gl::STREAM_DRAW);
+        gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+        let interior_mask = count_windings(outline.fill_rule, fan_len);
+
+        // mark the pixels that were not clipped yet but are outside of the outline
+        gl::StencilMask(CLIPPED_BIT);
+        gl::StencilFunc(gl::EQUAL, 0, CLIPPED_BIT | interior_mask);
+        gl::StencilOp(gl::KEEP, gl::KEEP, gl::INVERT);
+        gl::DrawArrays(gl::TRIANGLES, fan_len as GLsizei, 6);
+
+        gl::StencilMask(WINDING_BITS);
+        gl::StencilFunc(gl::ALWAYS, 0, 0xFF);
+        gl::StencilOp(gl::ZERO, gl::ZERO, gl::ZERO);
+        gl::DrawArrays(gl::TRIANGLES, fan_len as GLsizei, 6);
+        gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+    }
+    end_shapes();
+}
+
+/// Unmark every clipped pixel, then apply the clips that are in effect again.
+fn reset_clips(target: &GlRenderTarget) {
+    let vertices = canvas_quad(target);
+    begin_shapes(target);
+    unsafe {
+        gl::BufferData(gl::ARRAY_BUFFER, size_of_val(&vertices) as isize,
+            vertices.as_ptr() as *const c_void, gl::STREAM_DRAW);
+        gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+        gl::StencilMask(CLIPPED_BIT);
+        gl::StencilFunc(gl::ALWAYS, 0, 0xFF);
+        gl::StencilOp(gl::ZERO, gl::ZERO, gl::ZERO);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+        gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+    }
+    end_shapes();
+    for outline in &target.clips {
+        apply_clip(target, outline);
+    }
+}
+
+/// The product of every alpha in effect.
+fn current_alpha(target: &GlRenderTarget) -> f32 {
+    target.alphas.last().cloned().unwrap_or(1.0)
+}
+
+/// Start drawing onto a transparent layer with the clips in effect, full opacity and the default
+/// composite operation.
+fn push_group(target: &mut GlRenderTarget, alpha: f32) {
+    if target.layers.len() == target.groups.len() {
+        let layer = create_layer(target.width, target.height);
+        target.layers.push(layer);
+    }
+    target.groups.push(alpha);
+    target.alphas.push(1.0);
+    target.composites.push(CompositeOp::SourceOver);
+    use_composite(CompositeOp::SourceOver);
+    use_current_render_target(target);
+    clear((0.0, 0.0, 0.0, 0.0));
+    for outline in &target.clips {
+        apply_clip(target, outline);
+    }
+}
+
+/// Resolve the innermost group's layer and draw it onto the layer below with its opacity
+/// multiplied by the group's alpha.
+fn pop_group(target: &mut GlRenderTarget) {
+    let alpha = match target.groups.pop() {
+        Some(alpha) => alpha,
+        None => return,
+    };
+    target.alphas.pop();
+    target.composites.pop();
+    let layer = &target.layers[target.groups.len()];
+    resolve_layer(target, layer);
+    use_current_render_target(target);
+    let op = target.composites.last().cloned().unwrap_or_default();
+    draw_layer(target, layer, alpha * current_alpha(target), op);
+}
+
+/// The index of the canvas layer being drawn onto.
+fn current_layer(target: &GlRenderTarget) -> i32 {
+    target.layer_stack.last().cloned().unwrap_or(0)
+}
+
+/// Start drawing onto canvas layer `index`, creating it if it does not exist yet. Inside an
+/// opacity group, drawing stays on the group's layer.
+fn push_canvas_layer(target: &mut GlRenderTarget, index: i32) {
+    target.layer_stack.push(index);
+    if index != 0 && !target.canvas_layers.contains_key(&index) {
+        let layer = create_layer(target.width, target.height);
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, layer.ms_fbo) };
+        clear((0.0, 0.0, 0.0, 0.0));
+        target.canvas_layers.insert(index, layer);
+    }
+    switch_canvas_layer(target);
+}
+
+/// Go back to drawing onto the canvas layer that was drawn onto before the matching
+/// `push_canvas_layer`.
+fn pop_canvas_layer(target: &mut GlRenderTarget) {
+    if target.layer_stack.pop().is_some() {
+        switch_canvas_layer(target);
+    }
+}
+
+/// Bind the current canvas layer and apply the clips in effect to its stencil.
+fn switch_canvas_layer(target: &GlRenderTarget) {
+    if target.groups.is_empty() {
+        use_current_render_target(target);
+        reset_clips(target);
+    }
+}
+
+fn create_layer(width: i32, height: i32) -> Layer {
+    let (ms_fbo, ms_tex, ms_rbo) = create_ms_cds_render_target(width, height);
+    let (fbo, tex) = create_color_render_target(width, height);
+    Layer { ms_fbo, ms_tex, ms_rbo, fbo, tex }
+}
+
+/// Resolve a layer's multi-sampled FBO into its flat texture.
+fn resolve_layer(target: &GlRenderTarget, layer: &Layer) {
+    unsafe {
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, layer.ms_fbo);
+        gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, layer.fbo);
+        gl::BlitFramebuffer(0, 0, target.width, target.height, 0, 0, target.width, target.height,
+            gl::COLOR_BUFFER_BIT, gl::NEAREST);
+    }
+}
+
+/// Draw a layer's flat texture over the whole bound framebuffer with its opacity multiplied by
+/// `alpha`, using `op` and the clip in effect.
+fn draw_layer(target: &GlRenderTarget, layer: &Layer, alpha: f32, op: CompositeOp) {
+    // the layer's bottom row is the bottom of the canvas
+    let (x, y) = (target.width as f32 / 2.0, target.height as f32 / 2.0);
+    let max = i16::MAX;
+    let vertex = |x: f32, y: f32, uv: [i16; 2]| Vertex([x, y, 0.0], [255, 255, 255, 255], uv);
+    let vertices = [
+        vertex(-x, -y, [0, 0]),
+        vertex(x, -y, [max, 0]),
+        vertex(x, y, [max, max]),
+        vertex(x, y, [max, max]),
+        vertex(-x, y, [0, max]),
+        vertex(-x, -y, [0, 0]),
+    ];
+    begin_shapes(target);
+    unsafe {
+        // the layer already holds premultiplied colors
+        gl::Uniform1i(target.premultiply_loc, 0);
+        gl::Uniform1f(target.alpha_loc, alpha);
+        gl::Uniform1i(target.paint_kind_loc, 0);
+        gl::BindTexture(gl::TEXTURE_2D, layer.tex);
+        gl::BufferData(gl::ARRAY_BUFFER, size_of_val(&vertices) as isize,
+            vertices.as_ptr() as *const c_void, gl::STREAM_DRAW);
+        use_composite(op);
+        gl::DrawArrays(gl::TRIANGLES, 0, 6);
+        if op == CompositeOp::Multiply {
+            use_multiply_second_pass();
+            gl::DrawArrays(gl::TRIANGLES, 0, 6);
+            use_composite(op);
+        }
+    }
+    end_shapes();
+}
+
+/// Set up the paint uniforms and return the color vertices should have. `paint_transform` maps
+/// the paint's coordinates to canvas coordinates.
+///
+/// Gradients are sampled into the ramp texture and computed per fragment, so their vertices are
+/// white. Expects `begin_shapes` to have been called.
+fn use_paint(target: &GlRenderTarget, paint: &Paint, paint_transform: &Transform) -> Rgba {
+    let gradient = match *paint {
+        Paint::Color(c) => {
+            unsafe { gl::Uniform1i(target.paint_kind_loc, 0) };
+            return c;
+        }
+        Paint::Gradient(ref gradient) => gradient,
+    };
+    let inverse = match paint_transform.to_matrix3().invert() {
+        Some(inverse) => inverse,
+        None => {
+            unsafe { gl::Uniform1i(target.paint_kind_loc, 0) };
+            return (0.0, 0.0, 0.0, 0.0);
+        }
+    };
+    let (kind, params) = match gradient.kind {
+        GradientKind::Linear { x0, y0, x1, y1 } => (1, [x0, y0, x1, y1, 0.0, 0.0, 0.0, 0.0]),
+        GradientKind::Radial { x0, y0, r0, x1, y1, r1 } =>
+            (2, [x0, y0, r0, 0.0, x1, y1, r1, 0.0]),
+        GradientKind::Conic { start_angle, x, y } =>
+            (3, [start_angle, x, y, 0.0, 0.0, 0.0, 0.0, 0.0]),
+    };
+    let ramp = gradient_ramp(gradient);
+    unsafe {
+        gl::Uniform1i(target.paint_kind_loc, kind);
+        gl::UniformMatrix3fv(target.paint_matrix_loc, 1, gl::FALSE, inverse.as_ptr());
+        gl::Uniform4fv(target.paint_params_loc, 2, params.as_ptr());
+        gl::ActiveTexture(gl::TEXTURE1);
+        gl::BindTexture(gl::TEXTURE_2D, target.ramp_tex);
+        gl::TexSubImage2D(gl::TEXTURE_2D, 0, 0, 0, RAMP_SIZE as i32, 1, gl::RGBA,
+            gl::UNSIGNED_BYTE, ramp.as_ptr() as *const c_void);
+        gl::ActiveTexture(gl::TEXTURE0);
+    }
+    (1.0, 1.0, 1.0, 1.0)
+}
+
+/// Sample a gradient's colors at evenly spaced offsets from 0 to 1.
+fn gradient_ramp(gradient: &Gradient) -> Vec<[u8; 4]> {
+    (0..RAMP_SIZE)
+        .map(|i| color::to_bytes(gradient.color_at(i as f32 / (RAMP_SIZE - 1) as f32)))
+        .collect()
+}
+
+/// Queue flat colored triangles to be drawn with `texture` and `filter` along with the draws
+/// before them, drawing the queued vertices first if they need a different texture.
+///
+/// Everything else that changes how vertices are drawn, like the composite operation, the alpha
+/// or the clip, has to draw the batch with `flush_batch` before changing it.
+fn add_to_batch(target: &mut GlRenderTarget, texture: Tex, filter: GLint, vertices: &[Vertex]) {
+    if target.batch_texture != (texture, filter)
+            || target.batch.len() + vertices.len() > MAX_BATCH_VERTICES {
+        flush_batch(target);
+        target.batch_texture = (texture, filter);
+    }
+    target.batch.extend_from_slice(vertices);
+}
+
+/// Draw every batched vertex with a single draw call.
+fn flush_batch(target: &mut GlRenderTarget) {
+    if target.batch.is_empty() {
+        return;
+    }
+    let (texture, filter) = target.batch_texture;
+    begin_shapes(target);
+    unsafe {
+        gl::Uniform1i(target.paint_kind_loc, 0);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter);
+        // a new data store every time lets the driver keep drawing from the previous one
+        gl::BufferData(gl::ARRAY_BUFFER, (target.batch.len() * size_of::<Vertex>()) as isize,
+            target.batch.as_ptr() as *const c_void, gl::STREAM_DRAW);
+        gl::DrawArrays(gl::TRIANGLES, 0, target.batch.len() as GLsizei);
+    }
+    end_shapes();
+    target.batch.clear();
+}
+
+/// Whether a command only draws something that `add_to_batch` can queue, so it does not have
+/// to draw the batch first. The commands in draw lists and batches take care of that themselves.
+fn is_batchable(cmd: &DrawCmd) -> bool {
+    matches!(*cmd, DrawCmd::Rect { .. } | DrawCmd::Line { .. } | DrawCmd::Circle { .. }
+        | DrawCmd::Arc { .. } | DrawCmd::Path { .. } | DrawCmd::Image { .. }
+        | DrawCmd::List(..) | DrawCmd::Batch(_))
+}
+
+/// Tessellate and draw a shape command. Commands that are not shapes are ignored.
+///
+/// Flat colored shapes whose outline is a single convex polygon are batched, since their
+/// triangle fans cover them exactly. Anything else counts windings in the stencil buffer.
+fn draw_shape(target: &mut GlRenderTarget, cmd: &DrawCmd) {
+    if let Some(tessellation) = tessellate::tessellate(cmd) {
+        draw_tessellation(target, &tessellation);
+    }
+}
+
+fn draw_tessellation(target: &mut GlRenderTarget, tessellation: &Tessellation) {
+    if let Paint::Color(color) = tessellation.paint {
+        if tessellation.outline.is_convex() {
+            let vertices = outline_fans(&tessellation.outline, color::to_bytes(color));
+            let white_tex = target.white_tex;
+            add_to_batch(target, white_tex, gl::NEAREST as GLint, &vertices);
+            return;
+        }
+    }
+    flush_batch(target);
+    begin_shapes(target);
+    let color = use_paint(target, &tessellation.paint, &tessellation.paint_transform);
+    fill_outline(&tessellation.outline, color);
+    end_shapes();
+}
+
+/// Draw the commands of a draw list with `transform` applied after their own, reusing the
+/// list's tessellated shapes from earlier draws where possible.
+fn draw_list(target: &mut GlRenderTarget, list: Recording, transform: Transform) {
+    let cached = match target.lists.remove(&list.id()) {
+        Some(ref cached) if !same_linear_part(&cached.transform, &transform) => None,
+        cached => cached,
+    };
+    let cached = cached.unwrap_or_else(|| CachedList {
+        cmds: list.commands().iter().map(|cmd| match *cmd {
+            // text is drawn from the glyph atlas where possible
+            DrawCmd::Text { .. } => CachedCmd::Other(cmd.clone()),
+            _ => match tessellate::tessellate(&cmd.clone().transformed(transform)) {
+                Some(tessellation) => CachedCmd::Shape(tessellation),
+                None => CachedCmd::Other(cmd.clone()),
+            },
+        }).collect(),
+        recording: list,
+        transform,
+    });
+
+    let offset = Transform::from_translation(transform.e - cached.transform.e,
+        transform.f - cached.transform.f);
+    for cmd in &cached.cmds {
+        match *cmd {
+            CachedCmd::Shape(ref tessellation) if offset == Transform::identity() => {
+                draw_multiplied(target, |target| draw_tessellation(target, tessellation));
+            }
+            CachedCmd::Shape(ref tessellation) => {
+                let moved = Tessellation {
+                    outline: Outline {
+                        contours: tessellation.outline.contours.iter()
+                            .map(|c| c.iter().map(|&p| offset.transform_point(p)).collect())
+                            .collect(),
+                        fill_rule: tessellation.outline.fill_rule,
+                    },
+                    paint: tessellation.paint.clone(),
+                    paint_transform: tessellate::paint_transform(&tessellation.paint,
+                        &tessellation.paint_transform.then(offset)),
+                };
+                draw_multiplied(target, |target| draw_tessellation(target, &moved));
+            }
+            CachedCmd::Other(ref cmd) => {
+                draw_cmd(target, cmd.clone().transformed(transform));
+            }
+        }
+    }
+    target.lists.insert(cached.recording.id(), cached);
+}
+
+/// Whether two transforms only differ in their translation.
+fn same_linear_part(a: &Transform, b: &Transform) -> bool {
+    (a.a, a.b, a.c, a.d) == (b.a, b.b, b.c, b.d)
+}
+
+/// Forget the tessellated shapes of draw lists that can no longer be drawn.
+fn prune_lists(target: &mut GlRenderTarget) {
+    target.lists.retain(|_, cached| !cached.recording.is_unique());
+}
+
+/// Get the GL texture for a texture, uploading its pixels the first time it is drawn.
+fn texture_handle(target: &mut GlRenderTarget, texture: &Texture) -> Tex {
+    if let Some(&(_, tex)) = target.textures.get(&texture.id()) {
+        return tex;
+    }
+    let tex = unsafe {
+        let mut tex = 0;
+        gl::GenTextures(1, &mut tex);
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, texture.width() as i32,
+            texture.height() as i32, 0, gl::RGBA, gl::UNSIGNED_BYTE,
+            texture.pixels().as_ptr() as *const c_void);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+        tex
+    };
+    target.textures.insert(texture.id(), (texture.clone(), tex));
+    tex
+}
+
+/// Delete the GL textures of textures that can no longer be drawn.
+fn prune_textures(target: &mut GlRenderTarget) {
+    let unused: Vec<usize> = target.textures.iter()
+        .filter(|(_, (texture, _))| texture.is_unique())
+        .map(|(&id, _)| id)
+        .collect();
+    for id in unused {
+        if let Some((_, tex)) = target.textures.remove(&id) {
+            unsafe { gl::DeleteTextures(1, &tex) };
+        }
+    }
+}
+
+/// Draw part of a texture as a quad. Multi-sampling anti-aliases its edges.
+///
+/// Consecutive images of the same texture are batched.
+fn draw_image(target: &mut GlRenderTarget, image: &Image, transform: &Transform) {
+    let texture = &image.texture;
+    if texture.width() == 0 || texture.height() == 0 {
+        return;
+    }
+    let tex = texture_handle(target, texture);
+    let (src, dest) = (&image.src, &image.dest);
+    let (tw, th) = (texture.width() as f32, texture.height() as f32);
+    let uv = |x: f32, y: f32| {
+        let max = i16::MAX as f32;
+        [(x / tw * max) as i16, (y / th * max) as i16]
+    };
+    let vertex = |x: f32, y: f32, uv: [i16; 2]| {
+        let p = transform.transform_point(Point2::new(x, y));
+        Vertex([p.x, p.y, 0.0], [255, 255, 255, 255], uv)
+    };
+    // the top of the destination shows the top of the source
+    let (left, right) = (dest.x, dest.x + dest.width);
+    let (bottom, top) = (dest.y, dest.y + dest.height);
+    let (u0, u1) = (src.x, src.x + src.width);
+    let (v0, v1) = (src.y, src.y + src.height);
+    let vertices = [
+        vertex(left, bottom, uv(u0, v1)),
+        vertex(right, bottom, uv(u1, v1)),
+        vertex(right, top, uv(u1, v0)),
+        vertex(right, top, uv(u1, v0)),
+        vertex(left, top, uv(u0, v0)),
+        vertex(left, bottom, uv(u0, v1)),
+    ];
+    let filter = if image.smoothing { gl::LINEAR } else { gl::NEAREST } as i32;
+    add_to_batch(target, tex, filter, &vertices);
+}
+
+/// Draw filled text from glyphs rasterized into the glyph atlas.
+///
+/// Returns false, drawing nothing, if the command is not filled text or the text is too large to
+/// draw from the atlas; such text should be tessellated instead.
+fn draw_text(target: &mut GlRenderTarget, cmd: &DrawCmd) -> bool {
+    let (text, paint, transform) = match *cmd {
+        DrawCmd::Text { ref text, style: Style::FillStyle { ref paint, .. }, ref transform } =>
+            (text, paint, transform),
+        _ => return false,
+    };
+    // rasterize the glyphs at the size they appear on the screen
+    let scale = tessellate::scale_factor(transform);
+    if scale <= 0.0 || text.size * scale > MAX_ATLAS_FONT_SIZE {
+        return false;
+    }
+    // each glyph is paired with the start of its line's baseline
+    let mut glyphs = Vec::new();
+    for line in text.layout().lines {
+        let content = &text.content[line.range.clone()];
+        let line_glyphs = match text.font.layout(content, text.size * scale) {
+            Some(line_glyphs) => line_glyphs,
+            None => return false,
+        };
+        glyphs.extend(line_glyphs.into_iter().map(|glyph| (glyph, line.x, line.baseline)));
+    }
+    let font_id = text.font.id();
+    for (glyph, ..) in &glyphs {
+        target.glyph_cache.queue_glyph(font_id, glyph.clone());
+    }
+    let glyph_tex = target.glyph_tex;
+    let cached = target.glyph_cache.cache_queued(|rect, data| unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, glyph_tex);
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TexSubImage2D(gl::TEXTURE_2D, 0, rect.min.x as i32, rect.min.y as i32,
+            rect.width() as i32, rect.height() as i32, gl::RED, gl::UNSIGNED_BYTE,
+            data.as_ptr() as *const c_void);
+    });
+    if cached.is_err() {
+        return false;
+    }
+
+    let uv = |x: f32, y: f32| {
+        let max = i16::MAX as f32;
+        [(x * max) as i16, (y * max) as i16]
+    };
+    let mut vertices = Vec::new();
+    for &(ref glyph, line_x, baseline) in &glyphs {
+        let (uv_rect, screen_rect) = match target.glyph_cache.rect_for(font_id, glyph) {
+            Ok(Some(rects)) => rects,
+            _ => continue,
+        };
+        // glyph rects are in pixels with the y-axis pointing down
+        let vertex = |x: i32, y: i32, uv: [i16; 2]| {
+            let local = Point2::new(line_x + x as f32 / scale, baseline - y as f32 / scale);
+            let p = transform.transform_point(local);
+            Vertex([p.x, p.y, 0.0], [255, 255, 255, 255], uv)
+        };
+        let (min, max) = (screen_rect.min, screen_rect.max);
+        let (uv_min, uv_max) = (uv_rect.min, uv_rect.max);
+        vertices.push(vertex(min.x, max.y, uv(uv_min.x, uv_max.y)));
+        vertices.push(vertex(max.x, max.y, uv(uv_max.x, uv_max.y)));
+        vertices.push(vertex(max.x, min.y, uv(uv_max.x, uv_min.y)));
+        vertices.push(vertex(max.x, min.y, uv(uv_max.x, uv_min.y)));
+        vertices.push(vertex(min.x, min.y, uv(uv_min.x, uv_min.y)));
+        vertices.push(vertex(min.x, max.y, uv(uv_min.x, uv_max.y)));
+    }
+    if vertices.is_empty() {
+        return true;
+    }
+
+    begin_shapes(target);
+    let paint_transform = tessellate::paint_transform(paint, transform);
+    let color = color::to_bytes(use_paint(target, paint, &paint_transform));
+    for vertex in &mut vertices {
+        vertex.1 = color;
+    }
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, glyph_tex);
+        gl::BufferData(gl::ARRAY_BUFFER, (vertices.len() * size_of::<Vertex>()) as isize,
+            vertices.as_ptr() as *const c_void, gl::STREAM_DRAW);
+        gl::DrawArrays(gl::TRIANGLES, 0, vertices.len() as GLsizei);
+    }
+    end_shapes();
+    true
+}
+
 /// Parse DrawCmds into OpenGL commands.
 ///
 /// Updates the multi-sampled FBO and copies the result to the "flat" FBO. Returns whether or not 
 /// anything was drawn (e.g. if the currently shown framebuffer is "stale") and whether or not the
 /// window should keep running.
 ///
-/// Enables blending and Depth test.
+/// Once the canvas presents frames, the flat FBO is only updated when a frame is complete, and
+/// the framebuffer only counts as stale if a frame was completed since the last call.
+///
+/// Enables blending and disables the depth test; shapes are drawn in the order they arrive.
 pub fn parse_commands(target: &mut GlRenderTarget, rx: &Receiver<DrawCmd>) -> (bool, bool) {
-    enable_blending();
-    unsafe { gl::Enable(gl::DEPTH_TEST) };
-    use_ms_render_target(target);
+    use_composite(target.composites.last().cloned().unwrap_or_default());
+    unsafe { gl::Disable(gl::DEPTH_TEST) };
+    use_current_render_target(target);
     let mut stale = false;
     while let Ok(cmd) = rx.try_recv() {
         stale = true;
-        match cmd {
-            DrawCmd::Stop {} => {
-                return (false, true)
+        if !draw_cmd(target, cmd) {
+            return (false, true);
+        }
+    }
+    flush_batch(target);
+    prune_textures(target);
+    prune_lists(target);
+    if target.present_mode == Some(PresentMode::Frames) {
+        // the flat FBO was updated when the frame was presented
+        stale = target.frame_ready;
+        target.frame_ready = false;
+    } else if stale {
+        update_flat_target(target);
+    }
+    if stale {
+        use_default_target();
+    }
+    (stale, false)
+}
+
+/// Draw a single command into the bound FBO. Returns false if the command asked the canvas to
+/// stop.
+fn draw_cmd(target: &mut GlRenderTarget, cmd: DrawCmd) -> bool {
+    if !is_batchable(&cmd) {
+        flush_batch(target);
+    }
+    match cmd {
+        DrawCmd::Stop {} => {
+            return false;
+        }
+        DrawCmd::Clear(c) => {
+            // clearing also clears the stencil, so the clips have to be applied again
+            clear(c);
+            for outline in &target.clips {
+                apply_clip(target, outline);
             }
-            DrawCmd::Clear(c) => {
-                clear(c);
+        }
+        DrawCmd::PushClip(clip) => {
+            let outline = tessellate::clip_outline(&clip);
+            apply_clip(target, &outline);
+            target.clips.push(outline);
+        }
+        DrawCmd::PopClip {} => {
+            if target.clips.pop().is_some() {
+                reset_clips(target);
             }
-            DrawCmd::UsePostProcess(src) => {
-                if target.post_process_program.is_some() {
-                    unsafe {
-                        gl::DeleteProgram(target.post_process_program.unwrap());
-                    };
-                }
-                let new_shader = create_post_process_shader(&src);
+        }
+        DrawCmd::PushComposite(op) => {
+            use_composite(op);
+            target.composites.push(op);
+        }
+        DrawCmd::PopComposite {} => {
+            target.composites.pop();
+            use_composite(target.composites.last().cloned().unwrap_or_default());
+        }
+        DrawCmd::PushAlpha(alpha) => {
+            let alpha = alpha * current_alpha(target);
+            target.alphas.push(alpha);
+        }
+        DrawCmd::PopAlpha {} => {
+            target.alphas.pop();
+        }
+        DrawCmd::PushGroup(alpha) => {
+            push_group(target, alpha);
+        }
+        DrawCmd::PopGroup {} => {
+            pop_group(target);
+        }
+        DrawCmd::PushLayer(index) => {
+            push_canvas_layer(target, index);
+        }
+        DrawCmd::PopLayer {} => {
+            pop_canvas_layer(target);
+        }
+        DrawCmd::UsePostProcess(src) => {
+            if target.post_process_program.is_some() {
                 unsafe {
-                    target.post_process_res_loc =
-                        gl::GetUniformLocation(new_shader,
-                            b"resolution\0" as *const _ as *const i8);
-                    let diffuse_loc =
-                        gl::GetUniformLocation(new_shader, b"diffuse\0" as *const _ as *const i8);
-                    gl::UseProgram(new_shader);
-                    gl::Uniform1i(diffuse_loc, 0);
-                    gl::UseProgram(0);
+                    gl::DeleteProgram(target.post_process_program.unwrap());
+                };
+            }
+            let new_shader = create_post_process_shader(&src);
+            unsafe {
+                target.post_process_res_loc =
+                    gl::GetUniformLocation(new_shader,
+                        b"resolution\0" as *const _ as *const i8);
+                let diffuse_loc =
+                    gl::GetUniformLocation(new_shader, b"diffuse\0" as *const _ as *const i8);
+                let proj_loc = gl::GetUniformLocation(new_shader,
+                    b"proj_matrix\0" as *const _ as *const i8);
+                gl::UseProgram(new_shader);
+                gl::Uniform1i(diffuse_loc, 0);
+                // the post process shader only ever draws the screen quad
+                gl::UniformMatrix4fv(proj_loc, 1, gl::FALSE, Matrix4::identity().as_ptr());
+                gl::UseProgram(0);
+            }
+            target.post_process_program = Some(new_shader);
+        }
+        DrawCmd::Capture(path, reply) => {
+            // the flat FBO may hold a presented frame that has not been shown yet
+            if target.capture_target.is_none() {
+                target.capture_target = Some(create_color_render_target(target.width,
+                    target.height));
+            }
+            let (capture_fbo, _) = target.capture_target.unwrap();
+            composite_layers(target, capture_fbo);
+            let pixels = read_pixels(target, capture_fbo);
+            let result = snapshot::write_png(&path, target.width as u32,
+                target.height as u32, &pixels);
+            reply.send(result);
+            use_current_render_target(target);
+        }
+        DrawCmd::Present {} => {
+            let mode = *target.present_mode.get_or_insert(PresentMode::Frames);
+            if mode == PresentMode::Frames {
+                update_flat_target(target);
+                target.frame_ready = true;
+                use_current_render_target(target);
+            }
+        }
+        DrawCmd::SetPresentMode(mode) => {
+            target.present_mode = Some(mode);
+        }
+        DrawCmd::List(list, transform) => {
+            draw_list(target, list, transform);
+        }
+        DrawCmd::Batch(cmds) => {
+            for cmd in cmds {
+                if !draw_cmd(target, cmd) {
+                    return false;
                 }
-                target.post_process_program = Some(new_shader);
             }
-            _ => {}
+        }
+        DrawCmd::Image { image, transform, .. } => {
+            draw_multiplied(target, |target| draw_image(target, &image, &transform));
+        }
+        cmd @ DrawCmd::Text { .. } => {
+            draw_multiplied(target, |target| {
+                if !draw_text(target, &cmd) {
+                    draw_shape(target, &cmd);
+                }
+            });
+        }
+        cmd => {
+            draw_multiplied(target, |target| draw_shape(target, &cmd));
         }
     }
-    if stale {
-        update_flat_target(target);
-        use_default_target();
-    }
-    (stale, false)
+    true
 }
 
 /// Render the flat color texture to whatever framebuffer is currently bound.
@@ -558,7 +1768,17 @@ pub fn draw_flat_target(target: &GlRenderTarget) {
         enable_blending();
         gl::Disable(gl::DEPTH_TEST);
         clear((0.0, 0.0, 0.0, 1.0));
-        gl::UseProgram(target.post_process_program.unwrap_or(target.screen_program));
+        match target.post_process_program {
+            Some(program) => gl::UseProgram(program),
+            None => {
+                gl::UseProgram(target.screen_program);
+                let identity: Matrix4<f32> = Matrix4::identity();
+                gl::UniformMatrix4fv(target.proj_loc, 1, gl::FALSE, identity.as_ptr());
+                gl::Uniform1i(target.paint_kind_loc, 0);
+                gl::Uniform1i(target.premultiply_loc, 0);
+                gl::Uniform1f(target.alpha_loc, 1.0);
+            }
+        }
         if target.post_process_res_loc != -1 {
             gl::Uniform2i(target.post_process_res_loc, target.width, target.height);
         }
@@ -0,0 +1,235 @@
+//! Fonts for drawing text: TrueType and OpenType fonts, and the built-in Hershey simplex font.
+
+use shape::Path;
+use path::PathSegment;
+use hershey;
+
+use rusttype::{self, OutlineBuilder, PositionedGlyph, Scale};
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path as FsPath;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Id of the built-in Hershey simplex font.
+const HERSHEY_SIMPLEX_ID: usize = 0;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(HERSHEY_SIMPLEX_ID + 1);
+
+/// A font that text can be drawn with.
+///
+/// Cloning a font is cheap; clones share the same font data. Font sizes are given in canvas
+/// units per em, like CSS font sizes.
+#[derive(Clone)]
+pub struct Font {
+    data: Arc<FontData>,
+}
+
+struct FontData {
+    id: usize,
+    face: Face,
+}
+
+enum Face {
+    TrueType(rusttype::Font<'static>),
+    HersheySimplex,
+}
+
+/// The size of a piece of text, as returned by `Font::measure_text`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextMetrics {
+    /// How far the text advances along its baseline.
+    pub width: f32,
+    /// How far the font reaches above the baseline.
+    pub ascent: f32,
+    /// How far the font reaches below the baseline, as a positive distance.
+    pub descent: f32,
+}
+
+impl Font {
+    /// Parses a TrueType (.ttf) or OpenType (.otf) font. Font collections are not supported.
+    pub fn from_bytes(bytes: Vec<u8>) -> io::Result<Font> {
+        let font = rusttype::Font::try_from_vec(bytes).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "not a TrueType or OpenType font")
+        })?;
+        Ok(Font {
+            data: Arc::new(FontData {
+                id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                face: Face::TrueType(font),
+            }),
+        })
+    }
+
+    /// The built-in Hershey simplex font, which needs no font files.
+    ///
+    /// Its glyphs are single strokes, so text in this font has to be drawn with a stroke style;
+    /// like a line, it has no interior to fill.
+    pub fn hershey_simplex() -> Font {
+        Font {
+            data: Arc::new(FontData {
+                id: HERSHEY_SIMPLEX_ID,
+                face: Face::HersheySimplex,
+            }),
+        }
+    }
+
+    /// Loads a TrueType (.ttf) or OpenType (.otf) font from a file.
+    pub fn open<P: AsRef<FsPath>>(path: P) -> io::Result<Font> {
+        Font::from_bytes(fs::read(path)?)
+    }
+
+    /// A number that no other font created by this process shares.
+    pub fn id(&self) -> usize {
+        self.data.id
+    }
+
+    /// Whether the font's glyphs are strokes rather than outlines to be filled.
+    pub fn is_stroke_font(&self) -> bool {
+        match self.data.face {
+            Face::TrueType(_) => false,
+            Face::HersheySimplex => true,
+        }
+    }
+
+    /// Measures `content` drawn on a single line at `size`.
+    pub fn measure_text(&self, content: &str, size: f32) -> TextMetrics {
+        let font = match self.data.face {
+            Face::TrueType(ref font) => font,
+            Face::HersheySimplex => {
+                let scale = size / hershey::UNITS_PER_EM;
+                return TextMetrics {
+                    width: content.chars().map(hershey::advance).sum::<f32>() * scale,
+                    ascent: hershey::ASCENT * scale,
+                    descent: hershey::DESCENT * scale,
+                };
+            }
+        };
+        let v_metrics = font.v_metrics(scale(font, size));
+        let width = self.layout(content, size).unwrap_or_default().last()
+            .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+            .unwrap_or(0.0);
+        TextMetrics {
+            width,
+            ascent: v_metrics.ascent,
+            descent: -v_metrics.descent,
+        }
+    }
+
+    /// The outlines of `content` drawn on a single line at `size`, with the baseline starting at
+    /// `(x, y)`. For stroke fonts the path is made of open subpaths.
+    pub fn outline(&self, content: &str, x: f32, y: f32, size: f32) -> Path {
+        let glyphs = match self.layout(content, size) {
+            Some(glyphs) => glyphs,
+            None => return hershey::outline(content, x, y, size),
+        };
+        let mut builder = PathBuilder {
+            segments: Vec::new(),
+            x: 0.0,
+            y,
+        };
+        for glyph in glyphs {
+            builder.x = x + glyph.position().x;
+            glyph.unpositioned().build_outline(&mut builder);
+        }
+        Path { segments: builder.segments }
+    }
+
+    /// The distance from the start of `content`, drawn on a single line at `size`, to the left
+    /// edge of each of its characters, followed by the width of the whole line.
+    pub(crate) fn carets(&self, content: &str, size: f32) -> Vec<f32> {
+        let glyphs = match self.layout(content, size) {
+            Some(glyphs) => glyphs,
+            None => {
+                let scale = size / hershey::UNITS_PER_EM;
+                let mut x = 0.0;
+                let mut carets = vec![0.0];
+                for c in content.chars() {
+                    x += hershey::advance(c) * scale;
+                    carets.push(x);
+                }
+                return carets;
+            }
+        };
+        // rusttype lays out exactly one glyph per character
+        let mut carets: Vec<f32> = glyphs.iter().map(|g| g.position().x).collect();
+        let width = glyphs.last()
+            .map(|g| g.position().x + g.unpositioned().h_metrics().advance_width)
+            .unwrap_or(0.0);
+        carets.push(width);
+        carets
+    }
+
+    /// Lays out `content` on a single line at `size`, starting at the origin. Like all rusttype
+    /// coordinates, the glyph positions have the y-axis pointing down.
+    ///
+    /// Returns `None` for fonts that are not TrueType or OpenType fonts.
+    pub(crate) fn layout(&self, content: &str, size: f32)
+            -> Option<Vec<PositionedGlyph<'static>>> {
+        match self.data.face {
+            Face::TrueType(ref font) =>
+                Some(font.layout(content, scale(font, size), rusttype::point(0.0, 0.0)).collect()),
+            Face::HersheySimplex => None,
+        }
+    }
+}
+
+/// The rusttype scale for a size in units per em.
+fn scale(font: &rusttype::Font, size: f32) -> Scale {
+    let v_metrics = font.v_metrics_unscaled();
+    let height = v_metrics.ascent - v_metrics.descent;
+    Scale::uniform(size * height / font.units_per_em() as f32)
+}
+
+impl fmt::Debug for Font {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Font")
+            .field("id", &self.data.id)
+            .finish()
+    }
+}
+
+/// Fonts are equal if they share the same font data, i.e. one is a clone of the other.
+impl PartialEq for Font {
+    fn eq(&self, other: &Font) -> bool {
+        self.data.id == other.data.id
+    }
+}
+
+/// Collects glyph outlines, which rusttype gives with the y-axis pointing down, as path segments
+/// offset by `(x, y)`.
+struct PathBuilder {
+    segments: Vec<PathSegment>,
+    x: f32,
+    y: f32,
+}
+
+impl OutlineBuilder for PathBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let (x, y) = (self.x + x, self.y - y);
+        self.segments.push(PathSegment::MoveTo { x, y });
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let (x, y) = (self.x + x, self.y - y);
+        self.segments.push(PathSegment::LineTo { x, y });
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (cpx, cpy) = (self.x + x1, self.y - y1);
+        let (x, y) = (self.x + x, self.y - y);
+        self.segments.push(PathSegment::QuadraticCurveTo { cpx, cpy, x, y });
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (cp1x, cp1y) = (self.x + x1, self.y - y1);
+        let (cp2x, cp2y) = (self.x + x2, self.y - y2);
+        let (x, y) = (self.x + x, self.y - y);
+        self.segments.push(PathSegment::BezierCurveTo { cp1x, cp1y, cp2x, cp2y, x, y });
+    }
+
+    fn close(&mut self) {
+        self.segments.push(PathSegment::ClosePath);
+    }
+}
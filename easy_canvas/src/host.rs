@@ -1,6 +1,6 @@
 use shape::{DrawCmd};
 
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{channel, Sender, Receiver};
 
 /// A `Host` provides Canvases with a place to send events.
 ///
@@ -12,3 +12,223 @@ pub trait Host {
     /// Can fail if the host chooses to only allow one transmitter.
     fn sender(&mut self) -> Option<Sender<DrawCmd>>;
 }
+
+/// When a host that shows the canvas, like a window, presents what has been drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Everything is shown as soon as it has been drawn, even if the canvas is halfway through
+    /// drawing a frame. Calls to `Canvas::present` are ignored.
+    Immediate,
+    /// Drawing is only shown when the canvas calls `Canvas::present`, so animations never show
+    /// partially drawn frames.
+    Frames,
+}
+
+/// A `Host` that stores every command it receives, in the order they were sent.
+///
+/// Only the commands are recorded, not the `Canvas` calls that sent them. Most calls send a single
+/// `DrawCmd`, but scopes like `with_clip`, `with_alpha` or `with_layer` send a push before their
+/// closure runs and a pop after it, while `with_state`, `with_transform` and `with_style` send
+/// nothing and only change the commands sent from inside them. Handy for testing code written
+/// against `Target` without opening a window.
+pub struct RecordingHost {
+    tx: Sender<DrawCmd>,
+    rx: Receiver<DrawCmd>,
+    commands: Vec<DrawCmd>,
+}
+
+impl RecordingHost {
+    pub fn new() -> RecordingHost {
+        let (tx, rx) = channel();
+        RecordingHost {
+            tx,
+            rx,
+            commands: Vec::new(),
+        }
+    }
+
+    /// Every command received so far, oldest first.
+    pub fn commands(&mut self) -> &[DrawCmd] {
+        self.commands.extend(self.rx.try_iter());
+        &self.commands
+    }
+
+    /// Removes and returns every command received so far.
+    pub fn take_commands(&mut self) -> Vec<DrawCmd> {
+        self.commands.extend(self.rx.try_iter());
+        ::std::mem::take(&mut self.commands)
+    }
+}
+
+impl Default for RecordingHost {
+    fn default() -> RecordingHost {
+        RecordingHost::new()
+    }
+}
+
+impl Host for RecordingHost {
+    /// Always succeeds; every sender feeds the same recording.
+    fn sender(&mut self) -> Option<Sender<DrawCmd>> {
+        Some(self.tx.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canvas::{self, Target};
+    use draw_list::DrawList;
+    use shape::{ToDrawCmd, rect, circle, text};
+    use style::{Style, Stroke};
+    use font::Font;
+    use layout::TextOptions;
+    use transform::Transform;
+
+    fn shift(x: f32, y: f32) -> Transform {
+        Transform::from_translation(x, y)
+    }
+
+    #[test]
+    fn nested_scopes_compose_transforms() {
+        let mut host = RecordingHost::new();
+        let canvas = canvas::create(&mut host, 100, 100);
+        let red = Style::fill((255, 0, 0));
+        let blue = Style::fill((0, 0, 255));
+        canvas.with_state(shift(1.0, 0.0), red.clone(), |t| {
+            t.draw(rect(0.0, 0.0, 1.0, 1.0));
+            t.with_transform(shift(0.0, 2.0), |t| {
+                t.draw(circle(0.0, 0.0, 1.0));
+                t.with_style(blue.clone(), |t| t.draw(rect(0.0, 0.0, 2.0, 2.0)));
+            });
+            t.draw_with_override_transform(shift(5.0, 5.0), rect(0.0, 0.0, 3.0, 3.0));
+        });
+        let inner = shift(0.0, 2.0).then(shift(1.0, 0.0));
+        assert_eq!(host.take_commands(), vec![
+            DrawCmd::Rect { rect: rect(0.0, 0.0, 1.0, 1.0), style: red.clone(),
+                transform: shift(1.0, 0.0) },
+            DrawCmd::Circle { circle: circle(0.0, 0.0, 1.0), style: red.clone(),
+                transform: inner },
+            DrawCmd::Rect { rect: rect(0.0, 0.0, 2.0, 2.0), style: blue, transform: inner },
+            DrawCmd::Rect { rect: rect(0.0, 0.0, 3.0, 3.0), style: red,
+                transform: shift(5.0, 5.0) },
+        ]);
+    }
+
+    #[test]
+    fn clips_are_pushed_and_popped_in_pairs() {
+        let mut host = RecordingHost::new();
+        let canvas = canvas::create(&mut host, 100, 100);
+        let black = Style::fill((0, 0, 0));
+        canvas.with_clip(shift(1.0, 1.0), rect(0.0, 0.0, 4.0, 4.0), |t| {
+            t.with_clip(circle(0.0, 0.0, 2.0), |t| t.draw(rect(0.0, 0.0, 1.0, 1.0)));
+        });
+        assert_eq!(host.take_commands(), vec![
+            DrawCmd::PushClip(Box::new(rect(0.0, 0.0, 4.0, 4.0).with_state(shift(1.0, 1.0),
+                black.clone()))),
+            DrawCmd::PushClip(Box::new(circle(0.0, 0.0, 2.0).with_state(shift(1.0, 1.0),
+                black.clone()))),
+            DrawCmd::Rect { rect: rect(0.0, 0.0, 1.0, 1.0), style: black,
+                transform: shift(1.0, 1.0) },
+            DrawCmd::PopClip {},
+            DrawCmd::PopClip {},
+        ]);
+    }
+
+    #[test]
+    fn batches_are_sent_as_one_command() {
+        let mut host = RecordingHost::new();
+        let canvas = canvas::create(&mut host, 100, 100);
+        let black = Style::fill((0, 0, 0));
+        canvas.clear((255, 255, 255));
+        canvas.batch(|t| {
+            t.draw(shift(1.0, 0.0), black.clone(), rect(0.0, 0.0, 1.0, 1.0));
+            t.with_alpha(0.5, |t| t.draw(circle(0.0, 0.0, 1.0)));
+        });
+        assert_eq!(host.take_commands(), vec![
+            DrawCmd::Clear((1.0, 1.0, 1.0, 1.0)),
+            DrawCmd::Batch(vec![
+                rect(0.0, 0.0, 1.0, 1.0).with_state(shift(1.0, 0.0), black.clone()),
+                DrawCmd::PushAlpha(0.5),
+                circle(0.0, 0.0, 1.0).with_state(Transform::identity(), black),
+                DrawCmd::PopAlpha {},
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn transformed_moves_clips_lists_and_batches() {
+        let black = Style::fill((0, 0, 0));
+        let moved = |cmd: DrawCmd| cmd.transformed(shift(0.0, 3.0));
+        let shape = rect(0.0, 0.0, 1.0, 1.0).with_state(shift(1.0, 0.0), black.clone());
+        let shape_moved = rect(0.0, 0.0, 1.0, 1.0)
+            .with_state(shift(1.0, 0.0).then(shift(0.0, 3.0)), black);
+
+        assert_eq!(moved(DrawCmd::PushClip(Box::new(shape.clone()))),
+            DrawCmd::PushClip(Box::new(shape_moved.clone())));
+        assert_eq!(moved(DrawCmd::Batch(vec![shape.clone(), DrawCmd::PopClip {}])),
+            DrawCmd::Batch(vec![shape_moved, DrawCmd::PopClip {}]));
+        assert_eq!(moved(DrawCmd::PopClip {}), DrawCmd::PopClip {});
+
+        let list = DrawList::new();
+        list.draw(shift(1.0, 0.0), Style::fill((0, 0, 0)), rect(0.0, 0.0, 1.0, 1.0));
+        let recording = list.recording();
+        assert_eq!(moved(DrawCmd::List(recording.clone(), shift(2.0, 0.0))),
+            DrawCmd::List(recording, shift(2.0, 0.0).then(shift(0.0, 3.0))));
+    }
+
+    #[test]
+    fn text_can_be_drawn_onto_any_target() {
+        let mut host = RecordingHost::new();
+        let canvas = canvas::create(&mut host, 100, 100);
+        let font = Font::hershey_simplex();
+        let red = Style::fill((255, 0, 0));
+        let options = TextOptions::default();
+        let hi = text(font.clone(), "Hi".to_string(), 0.0, 0.0, 12.0, options);
+        canvas.fill_text(shift(1.0, 0.0), red.clone(), hi.clone());
+        canvas.stroke_text(shift(1.0, 0.0), red.clone(), hi.clone());
+        canvas.with_state(shift(1.0, 0.0), red.clone(), |t| {
+            t.fill_text(&font, "Hi", 0.0, 0.0, 12.0);
+            t.stroke_text(&font, "Hi", 0.0, 0.0, 12.0);
+        });
+        let list = DrawList::new();
+        list.fill_text(shift(1.0, 0.0), red.clone(), hi.clone());
+
+        let filled = hi.clone().with_state(shift(1.0, 0.0), red);
+        let stroked = hi.with_state(shift(1.0, 0.0),
+            Style::stroke_with((255, 0, 0), Stroke::default()));
+        assert_eq!(host.take_commands(),
+            vec![filled.clone(), stroked.clone(), filled.clone(), stroked]);
+        assert_eq!(list.into_commands(), vec![filled]);
+        assert_eq!(canvas.measure_text(&font, "Hi", 12.0), font.measure_text("Hi", 12.0));
+    }
+
+    #[test]
+    fn frames_are_presented_in_order() {
+        let mut host = RecordingHost::new();
+        let canvas = canvas::create(&mut host, 100, 100);
+        let shape = rect(0.0, 0.0, 1.0, 1.0);
+        canvas.set_present_mode(PresentMode::Frames);
+        canvas.draw(Transform::identity(), Style::fill((0, 0, 0)), shape.clone());
+        canvas.present();
+        canvas.set_present_mode(PresentMode::Immediate);
+        canvas.present();
+        assert_eq!(host.take_commands(), vec![
+            DrawCmd::SetPresentMode(PresentMode::Frames),
+            shape.with_state(Transform::identity(), Style::fill((0, 0, 0))),
+            DrawCmd::Present {},
+            DrawCmd::SetPresentMode(PresentMode::Immediate),
+            DrawCmd::Present {},
+        ]);
+    }
+
+    #[test]
+    fn draw_lists_are_sent_with_the_implicit_transform() {
+        let mut host = RecordingHost::new();
+        let canvas = canvas::create(&mut host, 100, 100);
+        let list = DrawList::new();
+        list.draw(Transform::identity(), Style::fill((0, 0, 0)), rect(0.0, 0.0, 1.0, 1.0));
+        canvas.with_transform(shift(4.0, 0.0), |t| t.draw_list(&list));
+        assert_eq!(host.take_commands(),
+            vec![DrawCmd::List(list.recording(), shift(4.0, 0.0))]);
+    }
+}
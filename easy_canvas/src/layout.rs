@@ -0,0 +1,320 @@
+//! Breaking text into lines and positioning them, like `textAlign` and `textBaseline` in HTML5.
+
+use shape::{Text, Path};
+
+use std::ops::Range;
+
+/// Where lines of text are placed horizontally relative to the text's position.
+///
+/// Text is always laid out left to right, so the start of a line is its left edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextAlign {
+    /// Lines start at the text's position.
+    #[default]
+    Start,
+    /// Lines are centered on the text's position.
+    Center,
+    /// Lines end at the text's position.
+    End,
+}
+
+/// Which part of the text sits at the text's vertical position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextBaseline {
+    /// The top of the first line, i.e. the font's ascent above its baseline.
+    Top,
+    /// Halfway between the top of the first line and the bottom of the last line.
+    Middle,
+    /// The baseline of the first line.
+    #[default]
+    Alphabetic,
+    /// The bottom of the last line, i.e. the font's descent below its baseline.
+    Bottom,
+}
+
+/// Describes how a piece of text is broken into lines and positioned.
+///
+/// Text always starts a new line at a newline character, which may follow a carriage return. By
+/// default lines are only broken there, and the first line's baseline starts at the text's
+/// position.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextOptions {
+    pub align: TextAlign,
+    pub baseline: TextBaseline,
+    /// Lines longer than `max_width` are broken at the last space that fits. Words that are
+    /// longer than `max_width` on their own are not broken, so they stick out.
+    pub max_width: Option<f32>,
+    /// The distance between the baselines of consecutive lines. Defaults to the font's ascent
+    /// plus its descent.
+    pub line_height: Option<f32>,
+}
+
+impl TextOptions {
+    pub fn align(self, align: TextAlign) -> TextOptions {
+        TextOptions { align, ..self }
+    }
+
+    pub fn baseline(self, baseline: TextBaseline) -> TextOptions {
+        TextOptions { baseline, ..self }
+    }
+
+    pub fn max_width(self, max_width: f32) -> TextOptions {
+        TextOptions { max_width: Some(max_width), ..self }
+    }
+
+    pub fn line_height(self, line_height: f32) -> TextOptions {
+        TextOptions { line_height: Some(line_height), ..self }
+    }
+}
+
+/// The lines a piece of text is broken into, as returned by `Text::layout`.
+///
+/// All positions are in the same coordinates as the text's position, so they are transformed
+/// along with the text when it is drawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    /// The lines from top to bottom.
+    pub lines: Vec<LineBox>,
+}
+
+/// One line of laid out text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineBox {
+    /// The byte range of the line's characters in the text's content. Newlines and the spaces at
+    /// which the line was broken are not part of any line.
+    pub range: Range<usize>,
+    /// The left edge of the line.
+    pub x: f32,
+    /// The height of the line's baseline.
+    pub baseline: f32,
+    pub width: f32,
+    /// How far the line reaches above its baseline.
+    pub ascent: f32,
+    /// How far the line reaches below its baseline, as a positive distance.
+    pub descent: f32,
+    /// The line's characters from left to right.
+    pub glyphs: Vec<GlyphBox>,
+}
+
+/// The position of a single character in a line of laid out text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphBox {
+    /// The byte offset of the character in the text's content.
+    pub index: usize,
+    /// The left edge of the character.
+    pub x: f32,
+    /// How far the line advances past the character, including any kerning with the next one.
+    pub advance: f32,
+}
+
+impl LineBox {
+    /// The top edge of the line.
+    pub fn top(&self) -> f32 {
+        self.baseline + self.ascent
+    }
+
+    /// The bottom edge of the line.
+    pub fn bottom(&self) -> f32 {
+        self.baseline - self.descent
+    }
+}
+
+impl TextLayout {
+    /// Finds the character under the point `(x, y)`, given in the same coordinates as the text.
+    ///
+    /// Returns the byte offset of the character in the text's content.
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<usize> {
+        let line = self.lines.iter().find(|line| line.bottom() <= y && y < line.top())?;
+        line.glyphs.iter()
+            .find(|glyph| glyph.x <= x && x < glyph.x + glyph.advance)
+            .map(|glyph| glyph.index)
+    }
+}
+
+impl Text {
+    /// Breaks the text into lines and positions them according to its options.
+    pub fn layout(&self) -> TextLayout {
+        let metrics = self.font.measure_text("", self.size);
+        let line_height = self.options.line_height.unwrap_or(metrics.ascent + metrics.descent);
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for paragraph in self.content.split('\n') {
+            // a carriage return before the newline belongs to it rather than to the line
+            let line = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+            self.break_paragraph(line, start, &mut lines);
+            start += paragraph.len() + 1;
+        }
+
+        let height = (lines.len() - 1) as f32 * line_height + metrics.ascent + metrics.descent;
+        let first_baseline = match self.options.baseline {
+            TextBaseline::Top => self.y - metrics.ascent,
+            TextBaseline::Middle => self.y + height / 2.0 - metrics.ascent,
+            TextBaseline::Alphabetic => self.y,
+            TextBaseline::Bottom => self.y - metrics.ascent + height,
+        };
+        for (i, line) in lines.iter_mut().enumerate() {
+            let x = match self.options.align {
+                TextAlign::Start => self.x,
+                TextAlign::Center => self.x - line.width / 2.0,
+                TextAlign::End => self.x - line.width,
+            };
+            for glyph in &mut line.glyphs {
+                glyph.x += x;
+            }
+            line.x = x;
+            line.baseline = first_baseline - i as f32 * line_height;
+            line.ascent = metrics.ascent;
+            line.descent = metrics.descent;
+        }
+        TextLayout { lines }
+    }
+
+    /// The outlines of every line of the text. For stroke fonts the path is made of open
+    /// subpaths.
+    pub fn outline(&self) -> Path {
+        let mut segments = Vec::new();
+        for line in self.layout().lines {
+            let content = &self.content[line.range];
+            segments.extend(self.font.outline(content, line.x, line.baseline, self.size).segments);
+        }
+        Path { segments }
+    }
+
+    /// Breaks a paragraph that starts at byte `offset` of the content into lines that start at
+    /// the origin.
+    fn break_paragraph(&self, paragraph: &str, offset: usize, lines: &mut Vec<LineBox>) {
+        let carets = self.font.carets(paragraph, self.size);
+        let chars: Vec<(usize, char)> = paragraph.char_indices().collect();
+        let line = |from: usize, to: usize| {
+            let left = carets[from];
+            LineBox {
+                range: offset + byte_offset(&chars, paragraph, from)
+                    ..offset + byte_offset(&chars, paragraph, to),
+                x: 0.0,
+                baseline: 0.0,
+                width: carets[to] - left,
+                ascent: 0.0,
+                descent: 0.0,
+                glyphs: (from..to).map(|i| GlyphBox {
+                    index: offset + chars[i].0,
+                    x: carets[i] - left,
+                    advance: carets[i + 1] - carets[i],
+                }).collect(),
+            }
+        };
+        let max_width = match self.options.max_width {
+            Some(max_width) => max_width,
+            None => {
+                lines.push(line(0, chars.len()));
+                return;
+            }
+        };
+
+        // the character ranges of the words, with leading spaces kept with the first word
+        let mut words: Vec<(usize, usize)> = Vec::new();
+        for (i, &(_, c)) in chars.iter().enumerate() {
+            if c.is_whitespace() {
+                continue;
+            }
+            match words.last_mut() {
+                Some(word) if word.1 == i => word.1 = i + 1,
+                _ => words.push((i, i + 1)),
+            }
+        }
+        match words.first_mut() {
+            Some(first) => first.0 = 0,
+            None => words.push((0, chars.len())),
+        }
+
+        let (mut from, mut to) = words[0];
+        for &(start, end) in &words[1..] {
+            if carets[end] - carets[from] > max_width {
+                lines.push(line(from, to));
+                from = start;
+            }
+            to = end;
+        }
+        lines.push(line(from, to));
+    }
+}
+
+/// The byte offset of the character at `index` in `paragraph`, or its length past the end.
+fn byte_offset(chars: &[(usize, char)], paragraph: &str, index: usize) -> usize {
+    chars.get(index).map(|&(offset, _)| offset).unwrap_or(paragraph.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use font::Font;
+    use shape::text;
+
+    fn layout(content: &str, options: TextOptions) -> TextLayout {
+        text(Font::hershey_simplex(), content.to_string(), 0.0, 0.0, 20.0, options).layout()
+    }
+
+    fn ranges(layout: &TextLayout) -> Vec<Range<usize>> {
+        layout.lines.iter().map(|line| line.range.clone()).collect()
+    }
+
+    #[test]
+    fn lines_break_at_newlines() {
+        let options = TextOptions::default();
+        assert_eq!(ranges(&layout("ab\ncd", options)), vec![0..2, 3..5]);
+        // carriage returns before a newline are not part of the line
+        assert_eq!(ranges(&layout("ab\r\ncd", options)), vec![0..2, 4..6]);
+        assert_eq!(ranges(&layout("ab\r\ncd", options.max_width(1000.0))), vec![0..2, 4..6]);
+        let lines = layout("ab\r\ncd", options).lines;
+        assert_eq!(lines[0].width, layout("ab", options).lines[0].width);
+    }
+
+    #[test]
+    fn lines_wrap_at_the_max_width() {
+        let words = layout("ab cd ef", TextOptions::default()).lines[0].clone();
+        // "ab cd" fits but "ab cd ef" does not
+        let fits = words.glyphs[4].x + words.glyphs[4].advance;
+        let options = TextOptions::default().max_width(fits + 0.1);
+        let wrapped = layout("ab cd ef", options);
+        assert_eq!(ranges(&wrapped), vec![0..5, 6..8]);
+        assert!(wrapped.lines.iter().all(|line| line.width <= fits + 0.1));
+        // the second line starts at the text's position again
+        assert_eq!(wrapped.lines[1].glyphs[0].x, 0.0);
+        assert!(wrapped.lines[1].baseline < wrapped.lines[0].baseline);
+    }
+
+    #[test]
+    fn words_longer_than_the_max_width_stick_out() {
+        let options = TextOptions::default().max_width(1.0);
+        let wrapped = layout("abcdef gh", options);
+        assert_eq!(ranges(&wrapped), vec![0..6, 7..9]);
+        assert!(wrapped.lines[0].width > 1.0);
+    }
+
+    #[test]
+    fn baselines_place_the_text_vertically() {
+        let content = "ab\ncd\nef";
+        let with = |baseline| layout(content, TextOptions::default().baseline(baseline)).lines;
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+
+        assert!(close(with(TextBaseline::Alphabetic)[0].baseline, 0.0));
+        assert!(close(with(TextBaseline::Top)[0].top(), 0.0));
+        assert!(close(with(TextBaseline::Bottom)[2].bottom(), 0.0));
+        let middle = with(TextBaseline::Middle);
+        assert!(close(middle[0].top() + middle[2].bottom(), 0.0));
+    }
+
+    #[test]
+    fn hit_tests_find_characters() {
+        let laid_out = layout("ab\ncd", TextOptions::default().align(TextAlign::Center));
+        let second = &laid_out.lines[1];
+        let d = second.glyphs[1];
+        assert_eq!(d.index, 4);
+        let y = second.baseline + 1.0;
+        assert_eq!(laid_out.hit_test(d.x + d.advance / 2.0, y), Some(4));
+        assert_eq!(laid_out.hit_test(second.x - 1.0, y), None);
+        assert_eq!(laid_out.hit_test(d.x, laid_out.lines[0].top() + 1.0), None);
+        let first = &laid_out.lines[0];
+        assert_eq!(laid_out.hit_test(first.x + 0.1, first.baseline), Some(0));
+    }
+}
@@ -9,23 +9,47 @@
 //! [host]: ./host/trait.Host.html
 
 extern crate cgmath;
+extern crate png;
+extern crate image;
+extern crate rusttype;
 #[cfg(feature = "drawing")]
 extern crate gl;
 
 pub mod color;
+pub mod paint;
+pub mod texture;
+pub mod font;
+pub mod layout;
+mod hershey;
 pub mod style;
+pub mod composite;
 pub mod shape;
+pub mod path;
 pub mod transform;
 pub mod canvas;
+pub mod draw_list;
 pub mod host;
+pub mod offscreen;
+pub mod tessellate;
+pub mod raster;
+pub mod snapshot;
+pub mod svg;
 #[cfg(feature = "drawing")]
 pub mod drawing;
 
 pub use color::*;
+pub use paint::*;
+pub use texture::*;
+pub use font::*;
+pub use layout::*;
 pub use style::*;
+pub use composite::*;
 pub use shape::*;
+pub use path::*;
 pub use transform::*;
 pub use canvas::*;
+pub use draw_list::*;
 pub use host::*;
+pub use offscreen::*;
 
 pub use cgmath::{Angle, Deg, Rad};
\ No newline at end of file
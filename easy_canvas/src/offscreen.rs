@@ -0,0 +1,136 @@
+//! Canvases that are drawn into memory and can be drawn onto other canvases as images.
+
+use shape::{DrawCmd, Image, Rect};
+use texture::Texture;
+use host::Host;
+use raster::Raster;
+
+use std::sync::mpsc::{channel, Sender, Receiver};
+
+/// A `Host` that draws into an in-memory pixel buffer, so the canvas it hosts can be drawn onto
+/// another canvas like an image.
+///
+/// Create a `Canvas` from it with `easy_canvas::create` and draw into it with the normal `Target`
+/// API. Commands are drawn when the offscreen canvas is turned into a texture or image, using the
+/// `raster` module, so drawing something expensive once and drawing the result every frame is
+/// cheap, e.g. for static backgrounds and minimaps. Draw `image()` onto another canvas with any
+/// transform to show the offscreen canvas there.
+pub struct Offscreen {
+    tx: Sender<DrawCmd>,
+    rx: Receiver<DrawCmd>,
+    raster: Raster,
+    /// The pixels as they were when the texture was last requested, unless something has been
+    /// drawn since
+    texture: Option<Texture>,
+}
+
+impl Offscreen {
+    /// Creates an offscreen canvas where every pixel is transparent black.
+    pub fn new(width: u32, height: u32) -> Offscreen {
+        let (tx, rx) = channel();
+        Offscreen {
+            tx,
+            rx,
+            raster: Raster::new(width, height),
+            texture: None,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.raster.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.raster.height()
+    }
+
+    /// Draws every command sent so far and returns the pixel buffer.
+    pub fn raster(&mut self) -> &Raster {
+        self.update();
+        &self.raster
+    }
+
+    /// Draws every command sent so far and returns the pixels as a texture.
+    ///
+    /// The texture is only recreated if something was drawn since the last call, so hosts do not
+    /// upload the pixels again while the offscreen canvas stays the same.
+    pub fn texture(&mut self) -> Texture {
+        self.update();
+        if self.texture.is_none() {
+            let pixels = self.raster.pixels().into_owned();
+            self.texture = Some(Texture::from_rgba(self.width(), self.height(), pixels));
+        }
+        self.texture.clone().unwrap()
+    }
+
+    /// Draws every command sent so far and returns an image shape of the whole canvas, centered
+    /// on the origin at its own size.
+    ///
+    /// Drawn with a translation, the offscreen canvas appears centered on that point, just like
+    /// it would look if it were a window of its own.
+    pub fn image(&mut self) -> Image {
+        let (width, height) = (self.width() as f32, self.height() as f32);
+        Image {
+            texture: self.texture(),
+            src: Rect { x: 0.0, y: 0.0, width, height },
+            dest: Rect { x: -width / 2.0, y: -height / 2.0, width, height },
+            smoothing: true,
+        }
+    }
+
+    fn update(&mut self) {
+        // a stopped canvas may still have drawn something before it stopped
+        let (stale, stopped) = self.raster.parse_commands(&self.rx);
+        if stale || stopped {
+            self.texture = None;
+        }
+    }
+}
+
+impl Host for Offscreen {
+    /// Always succeeds; every sender draws into the same pixel buffer.
+    fn sender(&mut self) -> Option<Sender<DrawCmd>> {
+        Some(self.tx.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canvas::{self, Target};
+    use style::Style;
+    use transform::Transform;
+
+    fn fill(offscreen: &mut Offscreen, color: (u8, u8, u8)) {
+        let canvas = canvas::create(offscreen, 4, 4);
+        let all = Rect { x: -2.0, y: -2.0, width: 4.0, height: 4.0 };
+        canvas.draw(Transform::identity(), Style::fill(color), all);
+    }
+
+    #[test]
+    fn images_composite_onto_other_rasters() {
+        let mut offscreen = Offscreen::new(4, 4);
+        fill(&mut offscreen, (255, 0, 0));
+        assert!(offscreen.raster().pixels().chunks(4).all(|p| p == [255, 0, 0, 255]));
+
+        // the image covers canvas x from 3 to 7 and y from -2 to 2
+        let mut raster = Raster::new(20, 20);
+        raster.draw_image(&offscreen.image(), &Transform::from_translation(5.0, 0.0));
+        assert_eq!(raster.pixel(15, 9), [255, 0, 0, 255]);
+        assert_eq!(raster.pixel(5, 9), [0, 0, 0, 0]);
+        assert_eq!(raster.pixel(15, 3), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn textures_follow_new_drawing() {
+        let mut offscreen = Offscreen::new(4, 4);
+        fill(&mut offscreen, (255, 0, 0));
+        let red = offscreen.texture();
+        assert_eq!(offscreen.texture().id(), red.id());
+
+        fill(&mut offscreen, (0, 0, 255));
+        let blue = offscreen.texture();
+        assert_ne!(blue.id(), red.id());
+        assert_eq!(blue.pixel(2, 2), [0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,189 @@
+//! Paints that shapes can be filled or stroked with: flat colors and gradients.
+
+use color::{Color, Rgba};
+
+use std::f32::consts::PI;
+
+/// What the inside of a filled shape or the outline of a stroked shape is colored with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+    Color(Rgba),
+    Gradient(Gradient),
+}
+
+/// Anything that can be used as a `Paint`: colors, gradients and paints themselves.
+pub trait ToPaint {
+    fn to_paint(self) -> Paint;
+}
+
+impl<C: Color> ToPaint for C {
+    fn to_paint(self) -> Paint {
+        Paint::Color(self.normalize())
+    }
+}
+
+impl ToPaint for Gradient {
+    fn to_paint(self) -> Paint {
+        Paint::Gradient(self)
+    }
+}
+
+impl ToPaint for Paint {
+    fn to_paint(self) -> Paint {
+        self
+    }
+}
+
+/// The geometry of a gradient, following the HTML5 Canvas gradient constructors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientKind {
+    /// Colors vary along the line from `(x0, y0)` to `(x1, y1)`.
+    Linear { x0: f32, y0: f32, x1: f32, y1: f32 },
+    /// Colors vary along a cone of circles that grows from the start circle to the end circle.
+    Radial { x0: f32, y0: f32, r0: f32, x1: f32, y1: f32, r1: f32 },
+    /// Colors sweep counter clockwise around `(x, y)`, starting at `start_angle` radians.
+    Conic { start_angle: f32, x: f32, y: f32 },
+}
+
+/// The coordinate system a gradient's geometry is given in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientSpace {
+    /// The shape's own coordinates; the gradient moves along with the draw's transform.
+    Local,
+    /// Canvas coordinates; the gradient stays put no matter how the shape is transformed.
+    Canvas,
+}
+
+/// A color gradient with any number of color stops.
+///
+/// Points before the first stop or after the last stop take the color of the nearest stop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    /// Offsets between 0 and 1 paired with their colors, sorted by offset.
+    pub stops: Vec<(f32, Rgba)>,
+    pub space: GradientSpace,
+}
+
+impl Gradient {
+    pub fn new(kind: GradientKind) -> Gradient {
+        Gradient {
+            kind,
+            stops: Vec::new(),
+            space: GradientSpace::Local,
+        }
+    }
+
+    pub fn linear(x0: f32, y0: f32, x1: f32, y1: f32) -> Gradient {
+        Gradient::new(GradientKind::Linear { x0, y0, x1, y1 })
+    }
+
+    pub fn radial(x0: f32, y0: f32, r0: f32, x1: f32, y1: f32, r1: f32) -> Gradient {
+        Gradient::new(GradientKind::Radial { x0, y0, r0, x1, y1, r1 })
+    }
+
+    pub fn conic(start_angle: f32, x: f32, y: f32) -> Gradient {
+        Gradient::new(GradientKind::Conic { start_angle, x, y })
+    }
+
+    /// Adds a color stop. Stops at the same offset are kept in the order they were added, which
+    /// makes a hard edge between their colors.
+    pub fn add_color_stop<C: Color>(mut self, offset: f32, color: C) -> Gradient {
+        let offset = offset.clamp(0.0, 1.0);
+        let index = self.stops.iter().position(|s| s.0 > offset).unwrap_or(self.stops.len());
+        self.stops.insert(index, (offset, color.normalize()));
+        self
+    }
+
+    /// Interprets the gradient's geometry in the given coordinate space.
+    pub fn in_space(self, space: GradientSpace) -> Gradient {
+        Gradient { space, ..self }
+    }
+
+    /// The color at `t`, where the first stop is at 0 and the last at 1.
+    pub fn color_at(&self, t: f32) -> Rgba {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return (0.0, 0.0, 0.0, 0.0),
+        };
+        if t <= first.0 {
+            return first.1;
+        }
+        if t >= last.0 {
+            return last.1;
+        }
+        for pair in self.stops.windows(2) {
+            let ((o0, c0), (o1, c1)) = (pair[0], pair[1]);
+            if t < o1 {
+                let a = if o1 > o0 { (t - o0) / (o1 - o0) } else { 1.0 };
+                let lerp = |x: f32, y: f32| x + (y - x) * a;
+                return (lerp(c0.0, c1.0), lerp(c0.1, c1.1), lerp(c0.2, c1.2), lerp(c0.3, c1.3));
+            }
+        }
+        last.1
+    }
+
+    /// The gradient parameter at a point in the gradient's coordinate space, or `None` if the
+    /// gradient does not cover the point (which can only happen for radial gradients).
+    pub fn param_at(&self, x: f32, y: f32) -> Option<f32> {
+        match self.kind {
+            GradientKind::Linear { x0, y0, x1, y1 } => {
+                let (dx, dy) = (x1 - x0, y1 - y0);
+                let len2 = dx * dx + dy * dy;
+                if len2 == 0.0 {
+                    None
+                } else {
+                    Some(((x - x0) * dx + (y - y0) * dy) / len2)
+                }
+            }
+            GradientKind::Radial { x0, y0, r0, x1, y1, r1 } => {
+                radial_param(x - x0, y - y0, r0, x1 - x0, y1 - y0, r1 - r0)
+            }
+            GradientKind::Conic { start_angle, x: cx, y: cy } => {
+                let angle = (y - cy).atan2(x - cx) - start_angle;
+                let t = angle / (2.0 * PI);
+                Some(t - t.floor())
+            }
+        }
+    }
+
+    /// The color at a point in the gradient's coordinate space.
+    pub fn color_at_point(&self, x: f32, y: f32) -> Rgba {
+        match self.param_at(x, y) {
+            Some(t) => self.color_at(t),
+            None => (0.0, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Solves for the largest `t` where the point `(px, py)` lies on the circle
+/// `center = t * (cx, cy)` with `radius = r0 + t * dr`, and that radius is not negative.
+///
+/// Points are relative to the center of the start circle.
+fn radial_param(px: f32, py: f32, r0: f32, cx: f32, cy: f32, dr: f32) -> Option<f32> {
+    let a = cx * cx + cy * cy - dr * dr;
+    let b = px * cx + py * cy + r0 * dr;
+    let c = px * px + py * py - r0 * r0;
+    let valid = |t: f32| r0 + t * dr >= 0.0;
+    if a.abs() <= 1e-6 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = c / (2.0 * b);
+        return if valid(t) { Some(t) } else { None };
+    }
+    let disc = b * b - a * c;
+    if disc < 0.0 {
+        return None;
+    }
+    let root = disc.sqrt();
+    let (t0, t1) = ((b + root) / a, (b - root) / a);
+    let (high, low) = if t0 > t1 { (t0, t1) } else { (t1, t0) };
+    if valid(high) {
+        Some(high)
+    } else if valid(low) {
+        Some(low)
+    } else {
+        None
+    }
+}
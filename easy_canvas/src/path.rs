@@ -0,0 +1,107 @@
+//! Builder methods for `Path`, modeled after the HTML5 Canvas path API.
+//!
+//! Angles are in radians from the positive x-axis. Since the y-axis points up, increasing angles
+//! go counter clockwise, so an arc drawn with `anticlockwise` set to false appears clockwise on
+//! screen, just like it would on an HTML5 canvas.
+
+use shape::Path;
+
+/// One step in the outline of a `Path`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    /// Starts a new subpath at the given point.
+    MoveTo { x: f32, y: f32 },
+    /// A straight line from the current point.
+    LineTo { x: f32, y: f32 },
+    /// A quadratic Bézier curve from the current point with one control point.
+    QuadraticCurveTo { cpx: f32, cpy: f32, x: f32, y: f32 },
+    /// A cubic Bézier curve from the current point with two control points.
+    BezierCurveTo { cp1x: f32, cp1y: f32, cp2x: f32, cp2y: f32, x: f32, y: f32 },
+    /// A circular arc, connected to the current point with a straight line.
+    Arc { x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32, anticlockwise: bool },
+    /// A circular arc tangent to the line from the current point to `(x1, y1)` and to the line
+    /// from `(x1, y1)` to `(x2, y2)`, connected to the current point with a straight line.
+    ArcTo { x1: f32, y1: f32, x2: f32, y2: f32, radius: f32 },
+    /// An elliptical arc whose x-axis is rotated by `rotation` radians, connected to the current
+    /// point with a straight line.
+    Ellipse {
+        x: f32,
+        y: f32,
+        radius_x: f32,
+        radius_y: f32,
+        rotation: f32,
+        start_angle: f32,
+        end_angle: f32,
+        anticlockwise: bool,
+    },
+    /// Closes the current subpath with a straight line back to its first point.
+    ClosePath,
+}
+
+impl Path {
+    /// Creates an empty path.
+    pub fn new() -> Path {
+        Path {
+            segments: Vec::new(),
+        }
+    }
+
+    fn with(mut self, segment: PathSegment) -> Path {
+        self.segments.push(segment);
+        self
+    }
+
+    pub fn move_to(self, x: f32, y: f32) -> Path {
+        self.with(PathSegment::MoveTo { x, y })
+    }
+
+    pub fn line_to(self, x: f32, y: f32) -> Path {
+        self.with(PathSegment::LineTo { x, y })
+    }
+
+    pub fn quadratic_curve_to(self, cpx: f32, cpy: f32, x: f32, y: f32) -> Path {
+        self.with(PathSegment::QuadraticCurveTo { cpx, cpy, x, y })
+    }
+
+    pub fn bezier_curve_to(self, cp1x: f32, cp1y: f32, cp2x: f32, cp2y: f32, x: f32, y: f32)
+            -> Path {
+        self.with(PathSegment::BezierCurveTo { cp1x, cp1y, cp2x, cp2y, x, y })
+    }
+
+    pub fn arc(self, x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32,
+            anticlockwise: bool) -> Path {
+        self.with(PathSegment::Arc { x, y, radius, start_angle, end_angle, anticlockwise })
+    }
+
+    pub fn arc_to(self, x1: f32, y1: f32, x2: f32, y2: f32, radius: f32) -> Path {
+        self.with(PathSegment::ArcTo { x1, y1, x2, y2, radius })
+    }
+
+    // mirrors the signature of `ellipse` in HTML5
+    #[allow(clippy::too_many_arguments)]
+    pub fn ellipse(self, x: f32, y: f32, radius_x: f32, radius_y: f32, rotation: f32,
+            start_angle: f32, end_angle: f32, anticlockwise: bool) -> Path {
+        self.with(PathSegment::Ellipse {
+            x, y, radius_x, radius_y, rotation, start_angle, end_angle, anticlockwise
+        })
+    }
+
+    /// Adds a closed rectangular subpath.
+    pub fn rect(self, x: f32, y: f32, width: f32, height: f32) -> Path {
+        self.move_to(x, y)
+            .line_to(x + width, y)
+            .line_to(x + width, y + height)
+            .line_to(x, y + height)
+            .close_path()
+    }
+
+    pub fn close_path(self) -> Path {
+        self.with(PathSegment::ClosePath)
+    }
+}
+
+impl Default for Path {
+    fn default() -> Path {
+        Path::new()
+    }
+}
@@ -0,0 +1,747 @@
+//! A software renderer that draws `DrawCmd`s into an in-memory RGBA8 pixel buffer.
+//!
+//! Needs neither a window nor an OpenGL context, which makes it suitable for tests and headless
+//! machines. Shapes are tessellated exactly like they are for the `drawing` module and blended
+//! the same way, so the output closely matches what `quick_window` shows on screen.
+
+use shape::{DrawCmd, Image};
+use color::{self, Rgba};
+use tessellate::{self, Tessellation, Outline};
+use transform::Transform;
+use paint::Paint;
+use style::FillRule;
+use composite::CompositeOp;
+use snapshot;
+
+use cgmath::Point2;
+
+use std::sync::mpsc::Receiver;
+use std::path::Path;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io;
+use std::mem;
+use std::ops::Range;
+
+/// Number of sub-scanlines sampled per pixel row, mirroring the multi-sampled GL target.
+const SAMPLES: usize = 4;
+
+/// An RGBA8 image that `DrawCmd`s can be rasterized into.
+///
+/// Pixels are stored row by row starting with the top row of the canvas. Like every canvas, the
+/// origin of the drawing coordinates is at the center of the image with the y-axis pointing up.
+pub struct Raster {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    /// How much of each pixel every clip in effect leaves visible, one mask per nested clip.
+    clips: Vec<Vec<f32>>,
+    /// The composite operations in effect, innermost last.
+    composites: Vec<CompositeOp>,
+    /// The product of every alpha in effect, innermost last.
+    alphas: Vec<f32>,
+    /// The pixels under each open opacity group and the group's alpha, innermost last.
+    groups: Vec<(Vec<u8>, f32)>,
+    /// The indices of the layers being drawn onto, innermost last.
+    layer_stack: Vec<i32>,
+    /// The index of the layer whose pixels are in `pixels`, or that is under the open opacity
+    /// groups.
+    layer: i32,
+    /// The pixels of every other layer that has been drawn onto.
+    layers: BTreeMap<i32, Vec<u8>>,
+}
+
+impl Raster {
+    /// Creates a raster where every pixel is transparent black.
+    pub fn new(width: u32, height: u32) -> Raster {
+        Raster {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 4],
+            clips: Vec::new(),
+            composites: Vec::new(),
+            alphas: Vec::new(),
+            groups: Vec::new(),
+            layer_stack: Vec::new(),
+            layer: 0,
+            layers: BTreeMap::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The RGBA8 pixel data, top row first.
+    ///
+    /// Only borrows the pixels if a single layer has been drawn onto; otherwise the layers are
+    /// composited into a new buffer.
+    pub fn pixels<'a>(&'a self) -> Cow<'a, [u8]> {
+        if self.layers.is_empty() {
+            Cow::Borrowed(&self.pixels)
+        } else {
+            Cow::Owned(self.flatten())
+        }
+    }
+
+    pub fn into_pixels(self) -> Vec<u8> {
+        if self.layers.is_empty() {
+            self.pixels
+        } else {
+            self.flatten()
+        }
+    }
+
+    /// Returns the pixel in column `x` and row `y`, counting from the top left corner, with
+    /// every layer composited like in `pixels`.
+    ///
+    /// Only this pixel is composited, so looking at single pixels stays cheap.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let pixel = self.flatten_range(i..i + 4);
+        [pixel[0], pixel[1], pixel[2], pixel[3]]
+    }
+
+    /// Executes a single command. Returns false if the command asked the canvas to stop.
+    ///
+    /// Post process shaders cannot be run without OpenGL and are ignored. Every command is drawn
+    /// as it arrives, so frame boundaries are ignored as well.
+    pub fn draw_cmd(&mut self, cmd: DrawCmd) -> bool {
+        match cmd {
+            DrawCmd::Stop {} => {
+                return false;
+            }
+            DrawCmd::Clear(c) => {
+                self.clear(c);
+            }
+            DrawCmd::UsePostProcess(_) => {}
+            DrawCmd::Present {} | DrawCmd::SetPresentMode(_) => {}
+            DrawCmd::Capture(path, reply) => {
+                reply.send(self.save_png(&path));
+            }
+            DrawCmd::PushClip(clip) => {
+                self.push_clip(&tessellate::clip_outline(&clip));
+            }
+            DrawCmd::PopClip {} => {
+                self.pop_clip();
+            }
+            DrawCmd::PushComposite(op) => {
+                self.composites.push(op);
+            }
+            DrawCmd::PopComposite {} => {
+                self.composites.pop();
+            }
+            DrawCmd::PushAlpha(alpha) => {
+                let alpha = alpha * self.alpha();
+                self.alphas.push(alpha);
+            }
+            DrawCmd::PopAlpha {} => {
+                self.alphas.pop();
+            }
+            DrawCmd::PushGroup(alpha) => {
+                self.push_group(alpha);
+            }
+            DrawCmd::PopGroup {} => {
+                self.pop_group();
+            }
+            DrawCmd::PushLayer(index) => {
+                self.layer_stack.push(index);
+                self.switch_layer();
+            }
+            DrawCmd::PopLayer {} => {
+                if self.layer_stack.pop().is_some() {
+                    self.switch_layer();
+                }
+            }
+            DrawCmd::List(list, transform) => {
+                let cmds = list.commands().iter().map(|cmd| cmd.clone().transformed(transform));
+                return self.draw_all(cmds);
+            }
+            DrawCmd::Batch(cmds) => {
+                return self.draw_all(cmds);
+            }
+            DrawCmd::Image { image, transform, .. } => {
+                self.draw_image(&image, &transform);
+            }
+            cmd => {
+                if let Some(tessellation) = tessellate::tessellate(&cmd) {
+                    self.fill(&tessellation);
+                }
+            }
+        }
+        true
+    }
+
+    /// Executes every command in `cmds`, stopping early if one asks the canvas to stop.
+    ///
+    /// Returns false if the canvas was stopped.
+    pub fn draw_all<I: IntoIterator<Item = DrawCmd>>(&mut self, cmds: I) -> bool {
+        for cmd in cmds {
+            if !self.draw_cmd(cmd) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Executes every command currently waiting in `rx`.
+    ///
+    /// Mirrors `drawing::parse_commands`: returns whether anything was drawn and whether the
+    /// canvas was asked to stop.
+    pub fn parse_commands(&mut self, rx: &Receiver<DrawCmd>) -> (bool, bool) {
+        let mut stale = false;
+        while let Ok(cmd) = rx.try_recv() {
+            stale = true;
+            if !self.draw_cmd(cmd) {
+                return (false, true);
+            }
+        }
+        (stale, false)
+    }
+
+    /// Writes the current pixels, with every layer composited, to a PNG file.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        snapshot::write_png(path, self.width, self.height, &self.pixels())
+    }
+
+    pub fn clear(&mut self, color: Rgba) {
+        let bytes = color::to_bytes(color);
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&bytes);
+        }
+    }
+
+    /// Fills a tessellated shape using its fill rule and paint.
+    pub fn fill(&mut self, tessellation: &Tessellation) {
+        let inverse = tessellation.paint_transform.invert();
+        self.fill_with(&tessellation.outline, |p| {
+            match (&tessellation.paint, inverse) {
+                (Paint::Color(c), _) => *c,
+                (Paint::Gradient(g), Some(inverse)) => {
+                    let p = inverse.transform_point(p);
+                    g.color_at_point(p.x, p.y)
+                }
+                (Paint::Gradient(_), None) => (0.0, 0.0, 0.0, 0.0),
+            }
+        });
+    }
+
+    /// Draws part of a texture into the image's destination rectangle.
+    pub fn draw_image(&mut self, image: &Image, transform: &Transform) {
+        let inverse = match transform.invert() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let outline = tessellate::image_outline(image, transform);
+        let (src, dest) = (&image.src, &image.dest);
+        self.fill_with(&outline, |p| {
+            let local = inverse.transform_point(p);
+            // the top of the destination shows the top of the source
+            let u = (local.x - dest.x) / dest.width;
+            let v = (dest.y + dest.height - local.y) / dest.height;
+            image.texture.sample(src.x + u * src.width, src.y + v * src.height, image.smoothing)
+        });
+    }
+
+    /// Restricts drawing to the interior of `outline` until the matching `pop_clip`, on top of
+    /// any clips already in effect.
+    pub fn push_clip(&mut self, outline: &Outline) {
+        let width = self.width as usize;
+        let mut mask = vec![0.0; width * self.height as usize];
+        rasterize(self.width, self.height, outline, |x, row, cover| {
+            mask[row * width + x] = cover;
+        });
+        if let Some(outer) = self.clips.last() {
+            for (m, outer) in mask.iter_mut().zip(outer) {
+                *m *= outer;
+            }
+        }
+        self.clips.push(mask);
+    }
+
+    /// Undoes the innermost clip.
+    pub fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    /// Starts drawing onto a new transparent layer until the matching `pop_group`.
+    ///
+    /// Drawing on the layer starts with full opacity and the default composite operation.
+    pub fn push_group(&mut self, alpha: f32) {
+        let below = vec![0; self.pixels.len()];
+        self.groups.push((mem::replace(&mut self.pixels, below), alpha));
+        self.alphas.push(1.0);
+        self.composites.push(CompositeOp::SourceOver);
+    }
+
+    /// Draws the innermost layer onto the pixels below it with its opacity multiplied by the
+    /// group's alpha.
+    pub fn pop_group(&mut self) {
+        let (below, alpha) = match self.groups.pop() {
+            Some(group) => group,
+            None => return,
+        };
+        self.alphas.pop();
+        self.composites.pop();
+        let layer = mem::replace(&mut self.pixels, below);
+        let alpha = alpha * self.alpha();
+        let clip = self.clips.last();
+        let op = self.composites.last().cloned().unwrap_or_default();
+        for (i, (pixel, src)) in self.pixels.chunks_mut(4).zip(layer.chunks(4)).enumerate() {
+            let cover = clip.map(|mask| mask[i]).unwrap_or(1.0);
+            if cover > 0.0 {
+                let color = (src[0] as f32 / 255.0, src[1] as f32 / 255.0, src[2] as f32 / 255.0,
+                    src[3] as f32 / 255.0 * alpha);
+                blend(pixel, color, cover, op);
+            }
+        }
+    }
+
+    /// Moves the pixels of the innermost layer scope's layer into `pixels`, creating the layer if
+    /// it has not been drawn onto yet. Inside an opacity group, drawing stays on the group.
+    fn switch_layer(&mut self) {
+        let index = self.layer_stack.last().cloned().unwrap_or(0);
+        if index == self.layer || !self.groups.is_empty() {
+            return;
+        }
+        let len = self.pixels.len();
+        let pixels = self.layers.remove(&index).unwrap_or_else(|| vec![0; len]);
+        let previous = mem::replace(&mut self.pixels, pixels);
+        self.layers.insert(self.layer, previous);
+        self.layer = index;
+    }
+
+    /// Composites every layer in order of its index.
+    fn flatten(&self) -> Vec<u8> {
+        self.flatten_range(0..self.pixels.len())
+    }
+
+    /// Composites the bytes in `range` of every layer in order of its index.
+    fn flatten_range(&self, range: Range<usize>) -> Vec<u8> {
+        let mut layers: Vec<(i32, &Vec<u8>)> =
+            self.layers.iter().map(|(&index, pixels)| (index, pixels)).collect();
+        let position = layers.iter().position(|&(index, _)| index > self.layer)
+            .unwrap_or(layers.len());
+        layers.insert(position, (self.layer, &self.pixels));
+
+        let mut flat = layers[0].1[range.clone()].to_vec();
+        for &(_, pixels) in &layers[1..] {
+            for (pixel, src) in flat.chunks_mut(4).zip(pixels[range.clone()].chunks(4)) {
+                if src[3] > 0 {
+                    let color = (src[0] as f32 / 255.0, src[1] as f32 / 255.0,
+                        src[2] as f32 / 255.0, src[3] as f32 / 255.0);
+                    blend(pixel, color, 1.0, CompositeOp::SourceOver);
+                }
+            }
+        }
+        flat
+    }
+
+    /// The product of every alpha in effect.
+    fn alpha(&self) -> f32 {
+        self.alphas.last().cloned().unwrap_or(1.0)
+    }
+
+    /// Fills an outline, coloring each pixel with `paint_at` evaluated at the pixel's center in
+    /// canvas coordinates.
+    fn fill_with<F: Fn(Point2<f32>) -> Rgba>(&mut self, outline: &Outline, paint_at: F) {
+        let (w, h) = (self.width as f32, self.height as f32);
+        let width = self.width as usize;
+        let clip = self.clips.last();
+        let op = self.composites.last().cloned().unwrap_or_default();
+        let alpha = self.alpha();
+        let pixels = &mut self.pixels;
+        rasterize(self.width, self.height, outline, |x, row, cover| {
+            let cover = match clip {
+                Some(mask) => cover * mask[row * width + x],
+                None => cover,
+            };
+            if cover > 0.0 {
+                let i = (row * width + x) * 4;
+                let center = Point2::new(x as f32 + 0.5 - w / 2.0, h / 2.0 - row as f32 - 0.5);
+                let color = paint_at(center);
+                blend(&mut pixels[i..i + 4], (color.0, color.1, color.2, color.3 * alpha), cover,
+                    op);
+            }
+        });
+    }
+}
+
+/// Calls `cover` with the column, row and coverage of every pixel of a `width` by `height` image
+/// that `outline` covers at least partially.
+fn rasterize<F: FnMut(usize, usize, f32)>(width: u32, height: u32, outline: &Outline,
+        mut cover: F) {
+    let (w, h) = (width as f32, height as f32);
+    let mut edges = Vec::new();
+    for contour in &outline.contours {
+        // degenerate transforms and the like produce NaNs, which cover nothing sensible
+        let finite = contour.iter().all(|p| p.x.is_finite() && p.y.is_finite());
+        if contour.len() < 3 || !finite {
+            continue;
+        }
+        for i in 0..contour.len() {
+            let (a, b) = (contour[i], contour[(i + 1) % contour.len()]);
+            // convert to pixel coordinates, where y points down
+            let (ax, ay) = (a.x + w / 2.0, h / 2.0 - a.y);
+            let (bx, by) = (b.x + w / 2.0, h / 2.0 - b.y);
+            if ay == by {
+                continue;
+            }
+            edges.push(if ay < by {
+                Edge { x0: ax, y0: ay, x1: bx, y1: by, winding: 1 }
+            } else {
+                Edge { x0: bx, y0: by, x1: ax, y1: ay, winding: -1 }
+            });
+        }
+    }
+    if edges.is_empty() {
+        return;
+    }
+
+    let top = edges.iter().map(|e| e.y0).fold(h, f32::min).max(0.0).floor() as usize;
+    let bottom = edges.iter().map(|e| e.y1).fold(0.0, f32::max).min(h).ceil() as usize;
+    let width = width as usize;
+    let mut coverage = vec![0.0f32; width + 1];
+    let mut crossings = Vec::new();
+
+    for row in top..bottom {
+        for c in coverage.iter_mut() {
+            *c = 0.0;
+        }
+        let row_edges: Vec<&Edge> = edges.iter()
+            .filter(|e| e.y0 < (row + 1) as f32 && e.y1 > row as f32)
+            .collect();
+        for sample in 0..SAMPLES {
+            let y = row as f32 + (sample as f32 + 0.5) / SAMPLES as f32;
+            crossings.clear();
+            for e in &row_edges {
+                if e.y0 <= y && y < e.y1 {
+                    let t = (y - e.y0) / (e.y1 - e.y0);
+                    crossings.push((e.x0 + (e.x1 - e.x0) * t, e.winding));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = match outline.fill_rule {
+                    FillRule::NonZero => winding != 0,
+                    FillRule::EvenOdd => winding % 2 != 0,
+                };
+                if inside {
+                    add_span(&mut coverage, pair[0].0, pair[1].0, 1.0 / SAMPLES as f32);
+                }
+            }
+        }
+        // full pixels inside a span were recorded as differences; accumulate them
+        let mut run = 0.0;
+        for (x, c) in coverage.iter().take(width).enumerate() {
+            run += c;
+            let c = run.clamp(0.0, 1.0);
+            if c > 0.0 {
+                cover(x, row, c);
+            }
+        }
+    }
+}
+
+struct Edge {
+    x0: f32,
+    y0: f32,
+    x1: f32,
+    y1: f32,
+    winding: i32,
+}
+
+/// Adds a horizontal span to a row of coverage differences, anti-aliasing its end points.
+///
+/// `coverage[x]` holds the change in coverage from pixel `x - 1` to pixel `x`.
+fn add_span(coverage: &mut [f32], from: f32, to: f32, weight: f32) {
+    let width = (coverage.len() - 1) as f32;
+    let (from, to) = (from.max(0.0).min(width), to.max(0.0).min(width));
+    if to <= from {
+        return;
+    }
+    let (first, last) = (from.floor(), to.floor());
+    let (i, j) = (first as usize, last as usize);
+    if i == j {
+        let amount = (to - from) * weight;
+        coverage[i] += amount;
+        coverage[i + 1] -= amount;
+        return;
+    }
+    let head = (first + 1.0 - from) * weight;
+    coverage[i] += head;
+    coverage[i + 1] += weight - head;
+    let tail = (to - last) * weight;
+    coverage[j] += tail - weight;
+    if j + 1 < coverage.len() {
+        coverage[j + 1] -= tail;
+    }
+}
+
+/// Combines `color` with a pixel using `op` the way the GL backend does, weighted by the
+/// fraction of the pixel that is covered.
+///
+/// Pixels are stored with straight alpha, but like the GL backend the colors are combined with
+/// premultiplied alpha.
+fn blend(pixel: &mut [u8], color: Rgba, coverage: f32, op: CompositeOp) {
+    let premultiply = |c: [f32; 4]| [c[0] * c[3], c[1] * c[3], c[2] * c[3], c[3]];
+    let src = premultiply([color.0, color.1, color.2, color.3]);
+    let dst = premultiply([pixel[0] as f32 / 255.0, pixel[1] as f32 / 255.0,
+        pixel[2] as f32 / 255.0, pixel[3] as f32 / 255.0]);
+    let mut out = [0.0; 4];
+    for i in 0..4 {
+        let (fs, fd) = op.factors(src, dst, i);
+        let blended = (src[i] * fs + dst[i] * fd).clamp(0.0, 1.0);
+        out[i] = dst[i] + (blended - dst[i]) * coverage;
+    }
+    let alpha = out[3];
+    for (i, d) in pixel.iter_mut().enumerate() {
+        let c = if i == 3 { alpha } else if alpha > 0.0 { out[i] / alpha } else { 0.0 };
+        *d = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canvas::Target;
+    use draw_list::DrawList;
+    use shape::{self, Rect, Line};
+    use style::{Style, Stroke, LineCap, LineJoin};
+    use snapshot::SnapshotReply;
+    use font::Font;
+    use layout::{TextOptions, TextAlign};
+    use shape::text;
+
+    /// Draws onto a 20x20 raster, so pixel `(x, y)` covers canvas x from `x - 10` to `x - 9`
+    /// and canvas y from `9 - y` to `10 - y`.
+    fn render<F: FnMut(&DrawList)>(mut draw: F) -> Raster {
+        let list = DrawList::new();
+        draw(&list);
+        let mut raster = Raster::new(20, 20);
+        assert!(raster.draw_all(list.into_commands()));
+        raster
+    }
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    fn alpha(raster: &Raster, x: u32, y: u32) -> u8 {
+        raster.pixel(x, y)[3]
+    }
+
+    #[test]
+    fn clear_fills_every_pixel() {
+        let raster = render(|t| t.clear((10, 20, 30)));
+        assert!(raster.pixels().chunks(4).all(|p| p == [10, 20, 30, 255]));
+    }
+
+    #[test]
+    fn fills_are_anti_aliased() {
+        let raster = render(|t| {
+            t.draw(Transform::identity(), Style::fill((255, 0, 0)), rect(-5.0, -5.0, 10.5, 10.0));
+        });
+        assert_eq!(raster.pixel(10, 10), [255, 0, 0, 255]);
+        // the right edge runs through the middle of column 15
+        let edge = alpha(&raster, 15, 10);
+        assert!(edge > 100 && edge < 155, "edge alpha {}", edge);
+        assert_eq!(raster.pixel(17, 10), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn stroke_caps() {
+        let cap = |cap| render(|t| {
+            let style = Style::stroke_with((0, 0, 0), Stroke::new(4.0).cap(cap));
+            t.draw(Transform::identity(), style, Line { x1: -5.0, y1: 0.0, x2: 5.0, y2: 0.0 });
+        });
+        let (butt, round, square) = (cap(LineCap::Butt), cap(LineCap::Round),
+            cap(LineCap::Square));
+        // column 15 lies just past the end of the line, column 16 just past a round cap
+        assert_eq!(alpha(&butt, 15, 9), 0);
+        assert_eq!(alpha(&square, 15, 9), 255);
+        assert_eq!(alpha(&square, 16, 9), 255);
+        assert!(alpha(&round, 15, 9) > 0);
+        assert!(alpha(&round, 16, 9) > 0 && alpha(&round, 16, 9) < 255);
+        assert_eq!(alpha(&square, 17, 9), 0);
+    }
+
+    #[test]
+    fn stroke_joins() {
+        let join = |join| render(|t| {
+            let style = Style::stroke_with((0, 0, 0), Stroke::new(4.0).join(join));
+            let corner = shape::Path::new().move_to(-6.0, 0.0).line_to(0.0, 0.0).line_to(0.0, -6.0);
+            t.draw(Transform::identity(), style, corner);
+        });
+        let (miter, round, bevel) = (join(LineJoin::Miter), join(LineJoin::Round),
+            join(LineJoin::Bevel));
+        // pixel (11, 8) is the outer corner of the join, from (1, 1) to (2, 2); the bevel runs
+        // diagonally from (0, 2) to (2, 0)
+        assert_eq!(alpha(&miter, 11, 8), 255);
+        assert!(alpha(&round, 11, 8) > 0 && alpha(&round, 11, 8) < 255);
+        assert!(alpha(&bevel, 11, 8) < 10);
+        assert_eq!(alpha(&miter, 10, 8), 255);
+        assert!((alpha(&bevel, 10, 8) as i32 - 128).abs() < 20);
+        assert!(alpha(&round, 10, 8) > alpha(&bevel, 10, 8));
+        // the inside of the corner is covered either way
+        for raster in &[&miter, &round, &bevel] {
+            assert_eq!(alpha(raster, 9, 10), 255);
+        }
+    }
+
+    #[test]
+    fn fill_rules() {
+        let fill = |rule| render(|t| {
+            let squares = shape::Path::new()
+                .rect(-8.0, -8.0, 16.0, 16.0)
+                .rect(-4.0, -4.0, 8.0, 8.0);
+            t.draw(Transform::identity(), Style::fill_with_rule((0, 0, 255), rule), squares);
+        });
+        let (non_zero, even_odd) = (fill(FillRule::NonZero), fill(FillRule::EvenOdd));
+        assert_eq!(non_zero.pixel(10, 10), [0, 0, 255, 255]);
+        assert_eq!(even_odd.pixel(10, 10), [0, 0, 0, 0]);
+        assert_eq!(non_zero.pixel(3, 10), [0, 0, 255, 255]);
+        assert_eq!(even_odd.pixel(3, 10), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn clips_restrict_drawing() {
+        let raster = render(|t| {
+            t.with_clip(Transform::identity(), rect(-2.0, -2.0, 4.0, 4.0), |t| {
+                t.draw_with_style(Style::fill((0, 255, 0)), rect(-10.0, -10.0, 20.0, 20.0));
+            });
+        });
+        assert_eq!(raster.pixel(10, 10), [0, 255, 0, 255]);
+        assert_eq!(raster.pixel(5, 10), [0, 0, 0, 0]);
+        assert_eq!(raster.pixel(10, 15), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn destination_out_erases() {
+        let raster = render(|t| {
+            t.draw(Transform::identity(), Style::fill((255, 0, 0)), rect(-10.0, -10.0, 20.0, 20.0));
+            t.with_composite(CompositeOp::DestinationOut, |t| {
+                t.draw(rect(-10.0, -10.0, 10.0, 20.0));
+            });
+        });
+        assert_eq!(alpha(&raster, 5, 10), 0);
+        assert_eq!(raster.pixel(15, 10), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn multiply_keeps_shapes_over_transparent_pixels() {
+        let raster = render(|t| {
+            let orange = Style::fill((255, 128, 0));
+            t.draw(Transform::identity(), orange, rect(-10.0, -10.0, 10.0, 20.0));
+            t.with_composite(CompositeOp::Multiply, |t| {
+                t.draw_with_style(Style::fill((128, 255, 255)), rect(-10.0, -10.0, 20.0, 20.0));
+            });
+        });
+        assert_eq!(raster.pixel(5, 10), [128, 128, 0, 255]);
+        assert_eq!(raster.pixel(15, 10), [128, 255, 255, 255]);
+        // layers and groups start out transparent
+        let layer = render(|t| t.with_layer(1, |t| t.with_composite(CompositeOp::Multiply, |t| {
+            t.draw_with_style(Style::fill((0, 0, 255)), rect(-10.0, -10.0, 20.0, 20.0));
+        })));
+        assert_eq!(layer.pixel(10, 10), [0, 0, 255, 255]);
+        let group = render(|t| t.with_opacity_group(1.0, |t| {
+            t.with_composite(CompositeOp::Multiply, |t| {
+                t.draw_with_style(Style::fill((0, 0, 255)), rect(-10.0, -10.0, 20.0, 20.0));
+            })
+        }));
+        assert_eq!(group.pixel(10, 10), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn opacity_groups_hide_overlaps() {
+        let overlapping = |t: &DrawList| {
+            t.draw(Transform::identity(), Style::fill((0, 0, 0)), rect(-8.0, -2.0, 10.0, 4.0));
+            t.draw(Transform::identity(), Style::fill((0, 0, 0)), rect(-2.0, -2.0, 10.0, 4.0));
+        };
+        let group = render(|t| t.with_opacity_group(0.5, |_| overlapping(t)));
+        let faded = render(|t| t.with_alpha(0.5, |_| overlapping(t)));
+        // column 4 is covered by one rectangle, column 10 by both
+        assert_eq!(alpha(&group, 4, 10), alpha(&group, 10, 10));
+        assert!((alpha(&group, 10, 10) as i32 - 128).abs() <= 1);
+        assert!(alpha(&faded, 10, 10) > alpha(&faded, 4, 10));
+    }
+
+    #[test]
+    fn layers_are_stacked_by_number() {
+        let raster = render(|t| {
+            t.with_layer(1, |t| {
+                t.draw_with_style(Style::fill((255, 0, 0)), rect(0.0, -10.0, 10.0, 20.0));
+            });
+            t.with_layer(-1, |t| {
+                t.draw_with_style(Style::fill((0, 255, 0)), rect(-10.0, -10.0, 20.0, 20.0));
+            });
+            t.draw(Transform::identity(), Style::fill((0, 0, 255)), rect(-10.0, -10.0, 15.0, 20.0));
+        });
+        // red on layer 1 covers blue on layer 0, which covers green on layer -1
+        assert_eq!(raster.pixel(12, 10), [255, 0, 0, 255]);
+        assert_eq!(raster.pixel(5, 10), [0, 0, 255, 255]);
+        let pixels = raster.pixels();
+        for (i, pixel) in pixels.chunks(4).enumerate() {
+            assert_eq!(raster.pixel(i as u32 % 20, i as u32 / 20), pixel);
+        }
+        // clearing a layer leaves the others alone
+        let raster = render(|t| {
+            t.draw(Transform::identity(), Style::fill((0, 0, 255)), rect(-10.0, -10.0, 10.0, 20.0));
+            t.with_layer(-1, |t| {
+                t.draw_with_style(Style::fill((0, 255, 0)), rect(-10.0, -10.0, 20.0, 20.0));
+                t.clear((0, 0, 0, 0.0));
+            });
+        });
+        assert_eq!(raster.pixel(5, 10), [0, 0, 255, 255]);
+        assert_eq!(raster.pixel(15, 10), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn captures_report_whether_they_were_saved() {
+        let dir = ::std::env::temp_dir();
+        let mut raster = Raster::new(2, 2);
+        let (reply, rx) = SnapshotReply::new();
+        let missing = dir.join("easy_canvas_missing_dir").join("snapshot.png");
+        assert!(raster.draw_cmd(DrawCmd::Capture(missing, reply)));
+        assert!(rx.recv().unwrap().is_err());
+
+        let (reply, rx) = SnapshotReply::new();
+        let path = dir.join("easy_canvas_raster_capture.png");
+        assert!(raster.draw_cmd(DrawCmd::Capture(path.clone(), reply)));
+        assert!(rx.recv().unwrap().is_ok());
+        let _ = ::std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn stroke_fonts_draw_their_outlines() {
+        let font = Font::hershey_simplex();
+        let stroke = Style::stroke_with((0, 0, 0), Stroke::new(1.5));
+        let drawn = |content: &str| {
+            let raster = render(|t| {
+                let options = TextOptions::default().align(TextAlign::Center);
+                let content = text(font.clone(), content.to_string(), 0.0, -6.0, 16.0, options);
+                t.stroke_text(Transform::identity(), stroke.clone(), content);
+            });
+            raster.pixels().chunks(4).filter(|p| p[3] > 0).count()
+        };
+        assert!(drawn("H") > 20, "{} pixels drawn", drawn("H"));
+        assert_eq!(drawn(" "), 0);
+    }
+
+    #[test]
+    fn degenerate_geometry_does_not_panic() {
+        let nan = f32::NAN;
+        let raster = render(|t| {
+            let transform = Transform::from_matrix(nan, 0.0, 0.0, 1.0, 0.0, 0.0);
+            t.draw(transform, Style::fill((0, 0, 0)), rect(-5.0, -5.0, 10.0, 10.0));
+            t.draw(Transform::identity(), Style::fill((0, 0, 0)), rect(nan, 0.0, 1.0, 1.0));
+        });
+        assert_eq!(raster.pixel(10, 10), [0, 0, 0, 0]);
+    }
+}
@@ -1,6 +1,16 @@
 use color::Rgba;
 use style::Style;
 use transform::Transform;
+use path::PathSegment;
+use texture::Texture;
+use font::Font;
+use composite::CompositeOp;
+use layout::TextOptions;
+use draw_list::Recording;
+use host::PresentMode;
+use snapshot::SnapshotReply;
+
+use std::path::PathBuf;
 
 pub trait ToDrawCmd {
     fn with_state(self, transform: Transform, style: Style) -> DrawCmd;
@@ -11,6 +21,7 @@ macro_rules! make_shapes {
         pub enum DrawCmd {
             ..,
             $(
+                $(#[$cmd_attr:meta])*
                 $cmd:ident $t:tt
             ),*,
         }
@@ -24,6 +35,7 @@ macro_rules! make_shapes {
             }
         )*
     ) => {
+        #[derive(Debug, Clone, PartialEq)]
         pub enum DrawCmd {
             $(
                 $struct_form {
@@ -34,12 +46,26 @@ macro_rules! make_shapes {
             ),*,
 
             $(
+                $(#[$cmd_attr])*
                 $cmd $t
             ),*,
         }
 
+        impl DrawCmd {
+            /// The transform of a shape, or `None` if the command is not a shape.
+            fn shape_transform_mut(&mut self) -> Option<&mut Transform> {
+                match *self {
+                    $(
+                        DrawCmd::$struct_form { ref mut transform, .. } => Some(transform),
+                    )*
+                    _ => None,
+                }
+            }
+        }
+
         $(
             $(#[$attr])*
+            #[derive(Debug, Clone, PartialEq)]
             pub struct $struct_form {
                 $(
                     pub $field: $field_ty
@@ -73,6 +99,23 @@ make_shapes! {
         ..,
         Clear(Rgba),
         UsePostProcess(String),
+        #[doc = "Saves a snapshot to the path. Every `SnapshotReply` compares equal, so two captures"]
+        #[doc = "are equal if they save to the same path, whoever is waiting for the result."]
+        Capture(PathBuf, SnapshotReply),
+        PushClip(Box<DrawCmd>),
+        PopClip {},
+        PushComposite(CompositeOp),
+        PopComposite {},
+        PushAlpha(f32),
+        PopAlpha {},
+        PushGroup(f32),
+        PopGroup {},
+        PushLayer(i32),
+        PopLayer {},
+        List(Recording, Transform),
+        Batch(Vec<DrawCmd>),
+        Present {},
+        SetPresentMode(PresentMode),
         Stop {},
     }
 
@@ -110,4 +153,58 @@ clockwise. Both angles are in radians."]
         start_angle: f32,
         end_angle: f32,
     }
+
+    #[doc = "An outline made of any number of subpaths of lines and curves.
+
+Usually built with the HTML5 Canvas style builder methods, e.g.
+`Path::new().move_to(0.0, 0.0).line_to(10.0, 0.0).line_to(0.0, 10.0).close_path()`."]
+    pub struct Path aka path {
+        segments: Vec<PathSegment>,
+    }
+
+    #[doc = "Part of a texture drawn into a rectangle, like `drawImage` in HTML5.
+
+`src` selects the part of the texture in pixels, measured from its top left corner with the
+y-axis pointing down. It is stretched to fill `dest`, which is given in canvas coordinates like
+any other `Rect`, with the top of the image at `dest.y + dest.height`. Without `smoothing` each
+texture pixel is drawn as a hard edged block, which suits pixel art.
+
+The style is ignored."]
+    pub struct Image aka image {
+        texture: Texture,
+        src: Rect,
+        dest: Rect,
+        smoothing: bool,
+    }
+
+    #[doc = "Text positioned at a point according to its options.
+
+`size` is the font size in canvas units per em. With the default options the text is drawn on a
+single line with its baseline starting at the point; see `TextOptions` for alignment and line
+breaking, and `Text::layout` for where each line and character ends up."]
+    pub struct Text aka text {
+        font: Font,
+        content: String,
+        x: f32,
+        y: f32,
+        size: f32,
+        options: TextOptions,
+    }
+}
+
+impl DrawCmd {
+    /// The command with `transform` applied after its own transform, which moves shapes, clips,
+    /// draw lists and batches. Other commands are returned unchanged.
+    pub fn transformed(mut self, transform: Transform) -> DrawCmd {
+        if let Some(own) = self.shape_transform_mut() {
+            *own = own.then(transform);
+        }
+        match self {
+            DrawCmd::PushClip(clip) => DrawCmd::PushClip(Box::new(clip.transformed(transform))),
+            DrawCmd::List(list, own) => DrawCmd::List(list, own.then(transform)),
+            DrawCmd::Batch(cmds) =>
+                DrawCmd::Batch(cmds.into_iter().map(|cmd| cmd.transformed(transform)).collect()),
+            cmd => cmd,
+        }
+    }
 }
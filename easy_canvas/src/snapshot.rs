@@ -0,0 +1,65 @@
+//! Encodes canvas snapshots as PNG files.
+
+use png::{self, HasParameters};
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Sender, Receiver};
+
+/// Where a host reports whether it managed to save a snapshot.
+///
+/// Sent along with `DrawCmd::Capture`. All replies compare equal, so captures compare by path.
+#[derive(Clone)]
+pub struct SnapshotReply {
+    tx: Arc<Mutex<Sender<io::Result<()>>>>,
+}
+
+impl SnapshotReply {
+    /// A reply and the receiver that the result is reported to.
+    pub fn new() -> (SnapshotReply, Receiver<io::Result<()>>) {
+        let (tx, rx) = channel();
+        (SnapshotReply { tx: Arc::new(Mutex::new(tx)) }, rx)
+    }
+
+    /// Reports the result of saving the snapshot. Nobody may be waiting for it anymore, which
+    /// is fine.
+    pub fn send(&self, result: io::Result<()>) {
+        if let Ok(tx) = self.tx.lock() {
+            let _ = tx.send(result);
+        }
+    }
+}
+
+impl fmt::Debug for SnapshotReply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SnapshotReply")
+    }
+}
+
+/// Replies are left out of comparisons, so commands compare by what they draw or save.
+impl PartialEq for SnapshotReply {
+    fn eq(&self, _other: &SnapshotReply) -> bool {
+        true
+    }
+}
+
+/// Writes RGBA8 `pixels`, top row first, to a PNG file at `path`.
+pub fn write_png<P: AsRef<Path>>(path: P, width: u32, height: u32, pixels: &[u8])
+        -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    encode_png(file, width, height, pixels)
+}
+
+/// Encodes RGBA8 `pixels`, top row first, as a PNG image into `out`.
+pub fn encode_png<W: Write>(out: W, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    assert_eq!(pixels.len(), width as usize * height as usize * 4,
+        "pixel buffer does not match the image dimensions");
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    Ok(())
+}
@@ -1,20 +1,182 @@
-use color::{Color, Rgba};
+use paint::{Paint, ToPaint};
 
-#[derive(Debug, Clone, Copy)]
+use std::sync::Arc;
+
+/// Decides which parts of a shape count as its interior when it is filled.
+///
+/// The rules only differ for shapes that overlap themselves or have several subpaths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    /// A point is inside if the outline winds around it a non-zero number of times.
+    #[default]
+    NonZero,
+    /// A point is inside if a ray from it crosses the outline an odd number of times, which
+    /// leaves holes where subpaths overlap.
+    EvenOdd,
+}
+
+/// The shape drawn at the open ends of a stroked line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    /// The stroke stops exactly at the end point.
+    Butt,
+    /// A half circle is added to each end.
+    Round,
+    /// The stroke extends past each end point by half the line width.
+    Square,
+}
+
+/// The shape drawn where two segments of a stroke meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    /// The outer edges are extended until they meet, up to the miter limit.
+    Miter,
+    /// The corner is rounded off with a circular arc.
+    Round,
+    /// The corner is cut off with a straight line.
+    Bevel,
+}
+
+/// Alternating lengths of dashes and gaps that a stroke is broken up into.
+///
+/// Like in HTML5 Canvas, a pattern with an odd number of lengths is repeated once so that it has
+/// an even number. Clones share the same lengths, so styles stay cheap to copy around, and solid
+/// strokes do not allocate at all.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Dash {
+    lengths: Option<Arc<[f32]>>,
+}
+
+impl Dash {
+    /// A dash pattern with the given lengths. An empty pattern draws a solid line.
+    pub fn new(pattern: &[f32]) -> Dash {
+        Dash {
+            lengths: if pattern.is_empty() { None } else { Some(Arc::from(pattern)) },
+        }
+    }
+
+    /// The pattern of a solid line, which has no lengths.
+    pub fn solid() -> Dash {
+        Dash { lengths: None }
+    }
+
+    pub fn lengths(&self) -> &[f32] {
+        match self.lengths {
+            Some(ref lengths) => lengths,
+            None => &[],
+        }
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.lengths.is_none()
+    }
+}
+
+/// Describes how a shape's outline is traced when it is stroked.
+///
+/// Widths and dash lengths are in shape-local units, so they are scaled along with the shape by
+/// its transform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stroke {
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    /// Miter joins longer than `miter_limit` times half the line width are drawn as bevels.
+    pub miter_limit: f32,
+    /// The dash pattern. Every subpath starts again at the beginning of the pattern.
+    pub dash: Dash,
+    /// How far into the dash pattern each subpath starts.
+    pub dash_offset: f32,
+}
+
+impl Stroke {
+    /// A stroke of the given width with the HTML5 Canvas defaults for everything else.
+    pub fn new(width: f32) -> Stroke {
+        Stroke {
+            width,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            miter_limit: 10.0,
+            dash: Dash::solid(),
+            dash_offset: 0.0,
+        }
+    }
+
+    pub fn cap(self, cap: LineCap) -> Stroke {
+        Stroke { cap, ..self }
+    }
+
+    pub fn join(self, join: LineJoin) -> Stroke {
+        Stroke { join, ..self }
+    }
+
+    pub fn miter_limit(self, miter_limit: f32) -> Stroke {
+        Stroke { miter_limit, ..self }
+    }
+
+    /// Sets the dash pattern. Zero length dashes with round or square caps draw dots.
+    pub fn dash(self, pattern: &[f32]) -> Stroke {
+        Stroke { dash: Dash::new(pattern), ..self }
+    }
+
+    /// Shifts the dash pattern; animating it makes the dashes crawl along the outline.
+    pub fn dash_offset(self, dash_offset: f32) -> Stroke {
+        Stroke { dash_offset, ..self }
+    }
+}
+
+impl Default for Stroke {
+    /// A one unit wide stroke with butt caps and miter joins.
+    fn default() -> Stroke {
+        Stroke::new(1.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Style {
     FillStyle {
-        color: Rgba
+        paint: Paint,
+        rule: FillRule,
     },
     StrokeStyle {
-        color: Rgba
+        paint: Paint,
+        stroke: Stroke,
     },
 }
 
 impl Style {
+    /// Fills shapes with a color or gradient.
+    #[inline]
+    pub fn fill<P: ToPaint>(paint: P) -> Style {
+        Style::fill_with_rule(paint, FillRule::NonZero)
+    }
+
     #[inline]
-    pub fn fill<C: Color>(color: C) -> Style {
+    pub fn fill_with_rule<P: ToPaint>(paint: P, rule: FillRule) -> Style {
         Style::FillStyle {
-            color: color.normalize()
+            paint: paint.to_paint(),
+            rule,
         }
     }
-}
\ No newline at end of file
+
+    /// A one unit wide stroke in a color or gradient.
+    #[inline]
+    pub fn stroke<P: ToPaint>(paint: P) -> Style {
+        Style::stroke_with(paint, Stroke::default())
+    }
+
+    #[inline]
+    pub fn stroke_with<P: ToPaint>(paint: P, stroke: Stroke) -> Style {
+        Style::StrokeStyle {
+            paint: paint.to_paint(),
+            stroke,
+        }
+    }
+
+    pub fn paint(&self) -> &Paint {
+        match *self {
+            Style::FillStyle { ref paint, .. } => paint,
+            Style::StrokeStyle { ref paint, .. } => paint,
+        }
+    }
+}
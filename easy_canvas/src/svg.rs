@@ -0,0 +1,861 @@
+//! Writes `DrawCmd`s out as an SVG document.
+//!
+//! Shapes are kept as SVG elements rather than being tessellated, so the output stays resolution
+//! independent. The document uses the same coordinate system as every other canvas: the origin
+//! is at the center and the y-axis points up.
+//!
+//! Linear and radial gradients become SVG gradients. Radial gradients whose start circle has a
+//! radius use the SVG 2 `fr` attribute, which SVG 1.1 renderers ignore. SVG has no conic
+//! gradients, so those are rendered into an image the size of the document, which fills shapes
+//! as a pattern.
+//!
+//! Images are embedded as PNG data URIs. They are linked with `xlink:href`, which SVG 1.1
+//! renderers need and SVG 2 renderers still accept.
+//!
+//! Of the composite operations, only `Lighter`, `Multiply` and `Screen` have SVG equivalents,
+//! which are CSS blend modes. `Lighter` becomes `plus-lighter`, which few SVG renderers support
+//! yet; most draw such shapes on top instead. SVG cannot erase what has been drawn, so shapes
+//! drawn with `DestinationIn`, `DestinationOut`, `Copy` or `Xor` are left out, rather than
+//! painting over what they should erase. Shapes drawn with any other operation are drawn on top.
+//!
+//! Alphas become `opacity` attributes, and opacity groups become groups with an opacity. Layers
+//! are written one after another in order of their index.
+
+use shape::{DrawCmd, Image, ToDrawCmd};
+use path::PathSegment;
+use style::{Style, FillRule, LineCap, LineJoin};
+use composite::CompositeOp;
+use paint::{Paint, Gradient, GradientKind, GradientSpace};
+use color::{self, Rgba};
+use transform::Transform;
+use tessellate::{self, Outline};
+use snapshot;
+
+use cgmath::{Point2, Matrix3, SquareMatrix};
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::fs::File;
+use std::io::{self, Write as IoWrite};
+use std::mem;
+use std::path::Path;
+use std::sync::mpsc::Receiver;
+use std::f32::consts::PI;
+
+/// An SVG document built from a stream of `DrawCmd`s.
+pub struct SvgDocument {
+    width: u32,
+    height: u32,
+    /// Gradient definitions referenced by the body
+    defs: String,
+    body: String,
+    /// Used to give every gradient and clip path definition a unique id
+    next_id: usize,
+    /// The definitions and opening tags of the clip and opacity groups that are open in the
+    /// body, innermost last
+    groups: Vec<(String, String)>,
+    /// The composite operations in effect, innermost last
+    composites: Vec<CompositeOp>,
+    /// The product of every alpha in effect, innermost last
+    alphas: Vec<f32>,
+    /// The open opacity groups as their index in `groups` and the length of `body` right after
+    /// their opening tag, innermost last
+    opacity_groups: Vec<(usize, usize)>,
+    /// The indices of the layers being drawn onto, innermost last
+    layer_stack: Vec<i32>,
+    /// The index of the layer whose elements are in `body`
+    layer: i32,
+    /// The elements of every other layer that has been drawn onto, with their groups closed
+    bodies: BTreeMap<i32, String>,
+    /// Everything that could not be added to the document, oldest first
+    errors: Vec<io::Error>,
+}
+
+impl SvgDocument {
+    pub fn new(width: u32, height: u32) -> SvgDocument {
+        SvgDocument {
+            width,
+            height,
+            defs: String::new(),
+            body: String::new(),
+            next_id: 0,
+            groups: Vec::new(),
+            composites: Vec::new(),
+            alphas: Vec::new(),
+            opacity_groups: Vec::new(),
+            layer_stack: Vec::new(),
+            layer: 0,
+            bodies: BTreeMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Adds a single command to the document. Returns false if the command asked the canvas to
+    /// stop.
+    ///
+    /// Post process shaders and frame boundaries have no SVG equivalent and are ignored. PNG
+    /// captures are answered with an error.
+    pub fn draw_cmd(&mut self, cmd: DrawCmd) -> bool {
+        match cmd {
+            DrawCmd::Stop {} => {
+                return false;
+            }
+            DrawCmd::Clear(c) => {
+                self.clear(c);
+            }
+            DrawCmd::UsePostProcess(_) => {}
+            DrawCmd::Present {} | DrawCmd::SetPresentMode(_) => {}
+            DrawCmd::Capture(_, reply) => {
+                reply.send(Err(io::Error::other("SVG documents cannot save PNG snapshots")));
+            }
+            DrawCmd::PushClip(clip) => {
+                self.push_clip(*clip);
+            }
+            DrawCmd::PopClip {} => {
+                self.pop_group();
+            }
+            DrawCmd::PushComposite(op) => {
+                self.composites.push(op);
+            }
+            DrawCmd::PopComposite {} => {
+                self.composites.pop();
+            }
+            DrawCmd::PushAlpha(alpha) => {
+                let alpha = alpha * self.alpha();
+                self.alphas.push(alpha);
+            }
+            DrawCmd::PopAlpha {} => {
+                self.alphas.pop();
+            }
+            DrawCmd::PushGroup(alpha) => {
+                let tag = format!(r#"<g opacity="{}"{}>"#, alpha * self.alpha(),
+                    self.blend_attr());
+                self.push_group(String::new(), tag);
+                self.opacity_groups.push((self.groups.len() - 1, self.body.len()));
+                // the group's contents start over with full opacity and the default operation
+                self.alphas.push(1.0);
+                self.composites.push(CompositeOp::SourceOver);
+            }
+            DrawCmd::PopGroup {} => {
+                self.alphas.pop();
+                self.composites.pop();
+                self.opacity_groups.pop();
+                self.pop_group();
+            }
+            DrawCmd::PushLayer(index) => {
+                self.layer_stack.push(index);
+                self.switch_layer();
+            }
+            DrawCmd::PopLayer {} => {
+                if self.layer_stack.pop().is_some() {
+                    self.switch_layer();
+                }
+            }
+            DrawCmd::List(list, transform) => {
+                let cmds = list.commands().iter().map(|cmd| cmd.clone().transformed(transform));
+                return self.draw_all(cmds);
+            }
+            DrawCmd::Batch(cmds) => {
+                return self.draw_all(cmds);
+            }
+            DrawCmd::Image { image, transform, .. } => {
+                self.image(&image, &transform);
+            }
+            // like lines, stroke fonts have no interior to fill
+            DrawCmd::Text { ref text, style: Style::FillStyle { .. }, .. }
+                    if text.font.is_stroke_font() => {}
+            cmd => {
+                if let Some((name, attrs, style, transform)) = shape_element(&cmd) {
+                    self.element(name, &attrs, style, transform);
+                }
+            }
+        }
+        true
+    }
+
+    /// Adds every command in `cmds`, stopping early if one asks the canvas to stop.
+    ///
+    /// Returns false if the canvas was stopped.
+    pub fn draw_all<I: IntoIterator<Item = DrawCmd>>(&mut self, cmds: I) -> bool {
+        for cmd in cmds {
+            if !self.draw_cmd(cmd) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Adds every command currently waiting in `rx`.
+    ///
+    /// Mirrors `drawing::parse_commands`: returns whether anything was drawn and whether the
+    /// canvas was asked to stop.
+    pub fn parse_commands(&mut self, rx: &Receiver<DrawCmd>) -> (bool, bool) {
+        let mut stale = false;
+        while let Ok(cmd) = rx.try_recv() {
+            stale = true;
+            if !self.draw_cmd(cmd) {
+                return (false, true);
+            }
+        }
+        (stale, false)
+    }
+
+    /// Everything that could not be added to the document so far, oldest first, e.g. images
+    /// that failed to encode.
+    pub fn errors(&self) -> &[io::Error] {
+        &self.errors
+    }
+
+    /// Writes the document to a file.
+    ///
+    /// Fails without writing anything if some of the drawing could not be added to the document;
+    /// see `errors`. The `Display` implementation writes the document regardless.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        if let Some(err) = self.errors.first() {
+            return Err(io::Error::new(err.kind(),
+                format!("the document is incomplete: {}", err)));
+        }
+        let mut file = File::create(path)?;
+        write!(file, "{}", self)
+    }
+
+    /// Defines a clip path and opens a group that uses it.
+    /// Replaces whatever has been drawn onto the innermost opacity group, or the layer outside of
+    /// one, with a rectangle covering the whole document.
+    fn clear(&mut self, c: Rgba) {
+        // inside an opacity group, only the group's contents are cleared, like the group's layer
+        // in the other backends
+        let (reopened, single_layer) = match self.opacity_groups.last() {
+            Some(&(group, start)) => {
+                self.body.truncate(start);
+                (group + 1, false)
+            }
+            None => {
+                self.body.clear();
+                (0, self.bodies.is_empty())
+            }
+        };
+        // everything drawn onto the layer so far would be hidden anyway; other layers may still
+        // use the definitions
+        if single_layer {
+            self.defs.clear();
+        }
+        let _ = writeln!(self.body, r#"<rect x="{}" y="{}" width="{}" height="{}" {}/>"#,
+            -(self.width as f32) / 2.0, -(self.height as f32) / 2.0, self.width, self.height,
+            color_attrs("fill", c));
+        // clearing is not clipped, but the clips and opacity groups stay in effect
+        for (i, (def, tag)) in self.groups.iter().enumerate() {
+            if single_layer {
+                self.defs.push_str(def);
+            }
+            if i >= reopened {
+                self.body.push_str(tag);
+            }
+        }
+    }
+
+    fn push_clip(&mut self, clip: DrawCmd) {
+        let id = format!("clip{}", self.next_id);
+        self.next_id += 1;
+        let mut def = String::new();
+        let _ = writeln!(def, r#"<clipPath id="{}">"#, id);
+        let shape = match clip {
+            DrawCmd::Image { ref image, transform, .. } =>
+                image.dest.clone().with_state(transform, Style::fill((0, 0, 0))),
+            ref clip => clip.clone(),
+        };
+        match shape {
+            // like lines, stroke fonts have no interior, so the clip path stays empty
+            DrawCmd::Text { ref text, style: Style::FillStyle { .. }, .. }
+                if text.font.is_stroke_font() => {}
+            ref shape => match shape_element(shape) {
+                Some((name, attrs, &Style::FillStyle { rule, .. }, transform)) => {
+                    let _ = writeln!(def, r#"<{} {} clip-rule="{}" transform="{}"/>"#, name,
+                        attrs, fill_rule_name(rule), matrix_attr(transform));
+                }
+                // clip paths ignore strokes, so the stroke's tessellated outline is used instead,
+                // which is what the other backends clip to
+                Some((_, _, &Style::StrokeStyle { .. }, _)) => {
+                    let outline = tessellate::clip_outline(shape);
+                    let _ = writeln!(def, r#"<path d="{}" clip-rule="{}"/>"#,
+                        outline_data(&outline), fill_rule_name(outline.fill_rule));
+                }
+                None => {}
+            },
+        }
+        let _ = writeln!(def, "</clipPath>");
+        self.push_group(def, format!(r#"<g clip-path="url(#{})">"#, id));
+    }
+
+    /// Adds `def` to the definitions and opens a group with the opening tag `tag`.
+    fn push_group(&mut self, def: String, tag: String) {
+        let tag = tag + "\n";
+        self.defs.push_str(&def);
+        self.body.push_str(&tag);
+        self.groups.push((def, tag));
+    }
+
+    /// Moves the elements of the innermost layer scope's layer into `body`, closing the open
+    /// groups in the previous layer and opening them again in the new one. Inside an opacity
+    /// group, drawing stays on the group.
+    fn switch_layer(&mut self) {
+        let index = self.layer_stack.last().cloned().unwrap_or(0);
+        if index == self.layer || !self.opacity_groups.is_empty() {
+            return;
+        }
+        for _ in &self.groups {
+            let _ = writeln!(self.body, "</g>");
+        }
+        let body = self.bodies.remove(&index).unwrap_or_default();
+        let previous = mem::replace(&mut self.body, body);
+        self.bodies.insert(self.layer, previous);
+        self.layer = index;
+        for (_, tag) in &self.groups {
+            self.body.push_str(tag);
+        }
+    }
+
+    /// Closes the innermost clip or opacity group.
+    fn pop_group(&mut self) {
+        if self.groups.pop().is_some() {
+            let _ = writeln!(self.body, "</g>");
+        }
+    }
+
+    fn element(&mut self, name: &str, attrs: &str, style: &Style, transform: &Transform) {
+        if self.erases() {
+            return;
+        }
+        let style = self.style_attrs(style, transform);
+        let _ = writeln!(self.body, r#"<{} {} {}{}{} transform="{}"/>"#, name, attrs, style,
+            self.opacity_attr(), self.blend_attr(), matrix_attr(transform));
+    }
+
+    /// The product of every alpha in effect.
+    fn alpha(&self) -> f32 {
+        self.alphas.last().cloned().unwrap_or(1.0)
+    }
+
+    /// An opacity attribute for the alpha in effect, if there is one.
+    fn opacity_attr(&self) -> String {
+        match self.alpha() {
+            alpha if alpha < 1.0 => format!(r#" opacity="{}""#, alpha),
+            _ => String::new(),
+        }
+    }
+
+    /// Whether the composite operation in effect erases or replaces the canvas, which SVG cannot
+    /// do. Whatever is drawn with it is left out.
+    fn erases(&self) -> bool {
+        matches!(self.composites.last(), Some(&CompositeOp::DestinationIn)
+            | Some(&CompositeOp::DestinationOut) | Some(&CompositeOp::Copy)
+            | Some(&CompositeOp::Xor))
+    }
+
+    /// A style attribute with the CSS blend mode of the composite operation in effect, if it has
+    /// one. Opacity groups drawn with an erasing operation are hidden instead.
+    fn blend_attr(&self) -> &'static str {
+        if self.erases() {
+            return r#" display="none""#;
+        }
+        match self.composites.last() {
+            Some(&CompositeOp::Lighter) => r#" style="mix-blend-mode:plus-lighter""#,
+            Some(&CompositeOp::Multiply) => r#" style="mix-blend-mode:multiply""#,
+            Some(&CompositeOp::Screen) => r#" style="mix-blend-mode:screen""#,
+            _ => "",
+        }
+    }
+
+    /// Adds an image, using a nested viewport to crop the texture to the source rectangle.
+    fn image(&mut self, image: &Image, transform: &Transform) {
+        if self.erases() {
+            return;
+        }
+        let texture = &image.texture;
+        let mut png = Vec::new();
+        if let Err(err) = snapshot::encode_png(&mut png, texture.width(), texture.height(),
+                texture.pixels()) {
+            self.errors.push(io::Error::new(err.kind(),
+                format!("failed to encode an image: {}", err)));
+            return;
+        }
+        let (src, dest) = (&image.src, &image.dest);
+        let rendering = if image.smoothing { "auto" } else { "pixelated" };
+        // the document's y-axis points up, so flip the image to keep it upright
+        let _ = writeln!(self.body,
+            r#"<g transform="{} matrix(1 0 0 -1 0 {})"{}{}>"#, matrix_attr(transform),
+            2.0 * dest.y + dest.height, self.opacity_attr(), self.blend_attr());
+        let _ = writeln!(self.body,
+            r#"<svg x="{}" y="{}" width="{}" height="{}" viewBox="{} {} {} {}" preserveAspectRatio="none">"#,
+            dest.x, dest.y, dest.width, dest.height, src.x, src.y, src.width, src.height);
+        let _ = writeln!(self.body,
+            r#"<image width="{}" height="{}" image-rendering="{}" style="image-rendering:{}" xlink:href="data:image/png;base64,{}"/>"#,
+            texture.width(), texture.height(), rendering, rendering, base64(&png));
+        let _ = writeln!(self.body, "</svg>");
+        let _ = writeln!(self.body, "</g>");
+    }
+
+    fn style_attrs(&mut self, style: &Style, transform: &Transform) -> String {
+        match *style {
+            Style::FillStyle { ref paint, rule } => {
+                format!(r#"{} fill-rule="{}" stroke="none""#,
+                    self.paint_attrs("fill", paint, transform), fill_rule_name(rule))
+            }
+            Style::StrokeStyle { ref paint, ref stroke } => {
+                let cap = match stroke.cap {
+                    LineCap::Butt => "butt",
+                    LineCap::Round => "round",
+                    LineCap::Square => "square",
+                };
+                let join = match stroke.join {
+                    LineJoin::Miter => "miter",
+                    LineJoin::Round => "round",
+                    LineJoin::Bevel => "bevel",
+                };
+                let mut attrs = format!(r#"fill="none" {} stroke-width="{}""#,
+                    self.paint_attrs("stroke", paint, transform), stroke.width);
+                let _ = write!(attrs, r#" stroke-linecap="{}" stroke-linejoin="{}""#, cap, join);
+                let _ = write!(attrs, r#" stroke-miterlimit="{}""#, stroke.miter_limit);
+                if !stroke.dash.is_solid() {
+                    let lengths: Vec<String> =
+                        stroke.dash.lengths().iter().map(|len| len.to_string()).collect();
+                    let _ = write!(attrs, r#" stroke-dasharray="{}" stroke-dashoffset="{}""#,
+                        lengths.join(" "), stroke.dash_offset);
+                }
+                attrs
+            }
+        }
+    }
+
+    /// Attributes that paint the fill or stroke (`attr`) of an element drawn with `transform`,
+    /// adding a gradient definition if needed.
+    fn paint_attrs(&mut self, attr: &str, paint: &Paint, transform: &Transform) -> String {
+        let gradient = match *paint {
+            Paint::Color(c) => return color_attrs(attr, c),
+            Paint::Gradient(ref gradient) => gradient,
+        };
+        if let GradientKind::Conic { .. } = gradient.kind {
+            return match self.conic_pattern(gradient, transform) {
+                Some(id) => format!(r#"{}="url(#{})""#, attr, id),
+                None => color_attrs(attr, (0.0, 0.0, 0.0, 0.0)),
+            };
+        }
+        // canvas space gradients have to undo the element's transform
+        let gradient_transform = match gradient.space {
+            GradientSpace::Local => String::new(),
+            GradientSpace::Canvas => match transform.to_matrix3().invert() {
+                Some(inverse) => format!(r#" gradientTransform="{}""#, matrix3_attr(&inverse)),
+                None => return color_attrs(attr, (0.0, 0.0, 0.0, 0.0)),
+            },
+        };
+        let id = format!("gradient{}", self.next_id);
+        self.next_id += 1;
+        let (name, geometry) = match gradient.kind {
+            GradientKind::Linear { x0, y0, x1, y1 } => ("linearGradient",
+                format!(r#"x1="{}" y1="{}" x2="{}" y2="{}""#, x0, y0, x1, y1)),
+            GradientKind::Radial { x0, y0, r0, x1, y1, r1 } => {
+                let mut geometry = format!(r#"fx="{}" fy="{}" cx="{}" cy="{}" r="{}""#, x0, y0,
+                    x1, y1, r1);
+                // most renderers only support focal points, not focal circles
+                if r0 != 0.0 {
+                    let _ = write!(geometry, r#" fr="{}""#, r0);
+                }
+                ("radialGradient", geometry)
+            }
+            GradientKind::Conic { .. } => unreachable!(),
+        };
+        let _ = writeln!(self.defs, r#"<{} id="{}" gradientUnits="userSpaceOnUse" {}{}>"#, name,
+            id, geometry, gradient_transform);
+        for &(offset, color) in &gradient.stops {
+            let [r, g, b, _] = color::to_bytes(color);
+            let _ = writeln!(self.defs,
+                r#"<stop offset="{}" stop-color="rgb({},{},{})" stop-opacity="{}"/>"#,
+                offset, r, g, b, color.3);
+        }
+        let _ = writeln!(self.defs, "</{}>", name);
+        format!(r#"{}="url(#{})""#, attr, id)
+    }
+
+    /// Defines a pattern that covers the document with an image of a conic gradient, as painted
+    /// onto an element drawn with `transform`, and returns its id.
+    ///
+    /// Returns `None` if nothing drawn with `transform` would be visible.
+    fn conic_pattern(&mut self, gradient: &Gradient, transform: &Transform) -> Option<String> {
+        let inverse = transform.invert()?;
+        let (width, height) = (self.width, self.height);
+        let (w, h) = (width as f32, height as f32);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for row in 0..height {
+            for x in 0..width {
+                let center = Point2::new(x as f32 + 0.5 - w / 2.0, h / 2.0 - row as f32 - 0.5);
+                let point = match gradient.space {
+                    GradientSpace::Local => inverse.transform_point(center),
+                    GradientSpace::Canvas => center,
+                };
+                pixels.extend_from_slice(&color::to_bytes(gradient.color_at_point(point.x,
+                    point.y)));
+            }
+        }
+        let mut png = Vec::new();
+        if let Err(err) = snapshot::encode_png(&mut png, width, height, &pixels) {
+            self.errors.push(io::Error::new(err.kind(),
+                format!("failed to encode a conic gradient: {}", err)));
+            return None;
+        }
+        let id = format!("gradient{}", self.next_id);
+        self.next_id += 1;
+        // the pattern's tile starts at the bottom left corner of the document, whose coordinates
+        // the element's transform maps its own coordinates to
+        let tile_transform = Transform::from_translation(-w / 2.0, -h / 2.0).then(inverse);
+        let _ = writeln!(self.defs,
+            r#"<pattern id="{}" patternUnits="userSpaceOnUse" width="{}" height="{}" patternTransform="{}">"#,
+            id, w, h, matrix_attr(&tile_transform));
+        // the document's y-axis points up, so flip the image to keep it upright
+        let _ = writeln!(self.defs,
+            r#"<image width="{}" height="{}" transform="matrix(1 0 0 -1 0 {})" preserveAspectRatio="none" xlink:href="data:image/png;base64,{}"/>"#,
+            w, h, h, base64(&png));
+        let _ = writeln!(self.defs, "</pattern>");
+        Some(id)
+    }
+}
+
+impl fmt::Display for SvgDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(f, r#"<svg xmlns="http://www.w3.org/2000/svg""#)?;
+        writeln!(f, r#"    xmlns:xlink="http://www.w3.org/1999/xlink" width="{w}" height="{h}""#,
+            w = self.width, h = self.height)?;
+        writeln!(f, r#"    viewBox="0 0 {} {}">"#, self.width, self.height)?;
+        // flip the y-axis and move the origin to the center
+        writeln!(f, r#"<g transform="matrix(1 0 0 -1 {} {})">"#, self.width as f32 / 2.0,
+            self.height as f32 / 2.0)?;
+        if !self.defs.is_empty() {
+            writeln!(f, "<defs>")?;
+            f.write_str(&self.defs)?;
+            writeln!(f, "</defs>")?;
+        }
+        // layers are drawn in order of their index
+        let above = self.bodies.range(self.layer + 1..);
+        for (_, body) in self.bodies.range(..self.layer) {
+            f.write_str(body)?;
+        }
+        f.write_str(&self.body)?;
+        for _ in &self.groups {
+            writeln!(f, "</g>")?;
+        }
+        for (_, body) in above {
+            f.write_str(body)?;
+        }
+        writeln!(f, "</g>")?;
+        writeln!(f, "</svg>")
+    }
+}
+
+/// The element name and geometry attributes of a shape, along with the style and transform it is
+/// drawn with. Returns `None` for commands that are not shapes and for images.
+fn shape_element(cmd: &DrawCmd) -> Option<(&'static str, String, &Style, &Transform)> {
+    let element = match *cmd {
+        DrawCmd::Rect { ref rect, ref style, ref transform } => {
+            let (x, w) = if rect.width < 0.0 {
+                (rect.x + rect.width, -rect.width)
+            } else {
+                (rect.x, rect.width)
+            };
+            let (y, h) = if rect.height < 0.0 {
+                (rect.y + rect.height, -rect.height)
+            } else {
+                (rect.y, rect.height)
+            };
+            let attrs = format!(r#"x="{}" y="{}" width="{}" height="{}""#, x, y, w, h);
+            ("rect", attrs, style, transform)
+        }
+        DrawCmd::Line { ref line, ref style, ref transform } => {
+            let attrs = format!(r#"x1="{}" y1="{}" x2="{}" y2="{}""#, line.x1, line.y1,
+                line.x2, line.y2);
+            ("line", attrs, style, transform)
+        }
+        DrawCmd::Circle { ref circle, ref style, ref transform } => {
+            let attrs = format!(r#"cx="{}" cy="{}" r="{}""#, circle.x, circle.y,
+                circle.radius);
+            ("circle", attrs, style, transform)
+        }
+        DrawCmd::Arc { ref arc, ref style, ref transform } => {
+            let sweep = tessellate::ccw_sweep(arc.start_angle, arc.end_angle);
+            let mut d = String::new();
+            ellipse_data(&mut d, true, (arc.x, arc.y), (arc.radius, arc.radius), 0.0,
+                arc.start_angle, sweep);
+            ("path", format!(r#"d="{}""#, d), style, transform)
+        }
+        DrawCmd::Path { ref path, ref style, ref transform } => {
+            let d = path_data(&path.segments);
+            ("path", format!(r#"d="{}""#, d.trim()), style, transform)
+        }
+        DrawCmd::Text { ref text, ref style, ref transform } => {
+            // glyphs become paths so the document does not depend on the font
+            let outline = text.outline();
+            let d = path_data(&outline.segments);
+            ("path", format!(r#"d="{}""#, d.trim()), style, transform)
+        }
+        _ => return None,
+    };
+    Some(element)
+}
+
+/// Appends an elliptical arc to path data `d`, starting with a move or line to its first point.
+fn ellipse_data(d: &mut String, move_to: bool, center: (f32, f32), radii: (f32, f32),
+        rotation: f32, start_angle: f32, sweep: f32) {
+    let (sin, cos) = rotation.sin_cos();
+    let point = |angle: f32| {
+        let (x, y) = (radii.0 * angle.cos(), radii.1 * angle.sin());
+        (center.0 + x * cos - y * sin, center.1 + x * sin + y * cos)
+    };
+    let start = point(start_angle);
+    let _ = write!(d, "{} {} {} ", if move_to { "M" } else { "L" }, start.0, start.1);
+    // a single elliptical arc command cannot describe a full turn
+    let pieces = (sweep.abs() / PI).ceil() as usize;
+    for i in 0..pieces {
+        let end = point(start_angle + sweep * (i + 1) as f32 / pieces as f32);
+        let _ = write!(d, "A {} {} {} 0 {} {} {} ", radii.0, radii.1, rotation.to_degrees(),
+            if sweep > 0.0 { 1 } else { 0 }, end.0, end.1);
+    }
+}
+
+/// Converts the contours of a tessellated outline to SVG path data.
+fn outline_data(outline: &Outline) -> String {
+    let mut d = String::new();
+    for contour in &outline.contours {
+        for (i, p) in contour.iter().enumerate() {
+            let _ = write!(d, "{} {} {} ", if i == 0 { "M" } else { "L" }, p.x, p.y);
+        }
+        if !contour.is_empty() {
+            d.push_str("Z ");
+        }
+    }
+    d
+}
+
+/// Converts path segments to SVG path data.
+fn path_data(segments: &[PathSegment]) -> String {
+    let mut d = String::new();
+    let mut current = None;
+    let mut start = (0.0, 0.0);
+    for segment in segments {
+        match *segment {
+            PathSegment::MoveTo { x, y } => {
+                let _ = write!(d, "M {} {} ", x, y);
+                current = Some((x, y));
+                start = (x, y);
+            }
+            PathSegment::LineTo { x, y } => {
+                let _ = write!(d, "{} {} {} ", if current.is_some() { "L" } else { "M" }, x, y);
+                if current.is_none() {
+                    start = (x, y);
+                }
+                current = Some((x, y));
+            }
+            PathSegment::QuadraticCurveTo { cpx, cpy, x, y } => {
+                if current.is_none() {
+                    let _ = write!(d, "M {} {} ", cpx, cpy);
+                    start = (cpx, cpy);
+                }
+                let _ = write!(d, "Q {} {} {} {} ", cpx, cpy, x, y);
+                current = Some((x, y));
+            }
+            PathSegment::BezierCurveTo { cp1x, cp1y, cp2x, cp2y, x, y } => {
+                if current.is_none() {
+                    let _ = write!(d, "M {} {} ", cp1x, cp1y);
+                    start = (cp1x, cp1y);
+                }
+                let _ = write!(d, "C {} {} {} {} {} {} ", cp1x, cp1y, cp2x, cp2y, x, y);
+                current = Some((x, y));
+            }
+            PathSegment::Arc { x, y, radius, start_angle, end_angle, anticlockwise } => {
+                let sweep = tessellate::path_sweep(start_angle, end_angle, anticlockwise);
+                ellipse_data(&mut d, current.is_none(), (x, y), (radius, radius), 0.0,
+                    start_angle, sweep);
+                let end = start_angle + sweep;
+                if current.is_none() {
+                    start = (x + radius * start_angle.cos(), y + radius * start_angle.sin());
+                }
+                current = Some((x + radius * end.cos(), y + radius * end.sin()));
+            }
+            PathSegment::Ellipse { x, y, radius_x, radius_y, rotation, start_angle, end_angle,
+                    anticlockwise } => {
+                let sweep = tessellate::path_sweep(start_angle, end_angle, anticlockwise);
+                let (sin, cos) = rotation.sin_cos();
+                let point = |angle: f32| {
+                    let (ex, ey) = (radius_x * angle.cos(), radius_y * angle.sin());
+                    (x + ex * cos - ey * sin, y + ex * sin + ey * cos)
+                };
+                ellipse_data(&mut d, current.is_none(), (x, y), (radius_x, radius_y), rotation,
+                    start_angle, sweep);
+                if current.is_none() {
+                    start = point(start_angle);
+                }
+                current = Some(point(start_angle + sweep));
+            }
+            PathSegment::ArcTo { x1, y1, x2, y2, radius } => {
+                let from = match current {
+                    Some(p) => p,
+                    None => {
+                        let _ = write!(d, "M {} {} ", x1, y1);
+                        start = (x1, y1);
+                        (x1, y1)
+                    }
+                };
+                let geometry = tessellate::arc_to_geometry(Point2::new(from.0, from.1),
+                    Point2::new(x1, y1), Point2::new(x2, y2), radius);
+                match geometry {
+                    Some(arc) => {
+                        let _ = write!(d, "L {} {} A {r} {r} 0 0 {} {} {} ", arc.start.x,
+                            arc.start.y, if arc.sweep > 0.0 { 1 } else { 0 }, arc.end.x,
+                            arc.end.y, r = radius);
+                        current = Some((arc.end.x, arc.end.y));
+                    }
+                    None => {
+                        let _ = write!(d, "L {} {} ", x1, y1);
+                        current = Some((x1, y1));
+                    }
+                }
+            }
+            PathSegment::ClosePath => {
+                if current.is_some() {
+                    d.push_str("Z ");
+                    current = Some(start);
+                }
+            }
+        }
+    }
+    d
+}
+
+fn matrix_attr(transform: &Transform) -> String {
+    matrix3_attr(&transform.to_matrix3())
+}
+
+fn matrix3_attr(m: &Matrix3<f32>) -> String {
+    format!("matrix({} {} {} {} {} {})", m.x.x, m.x.y, m.y.x, m.y.y, m.z.x, m.z.y)
+}
+
+fn fill_rule_name(rule: FillRule) -> &'static str {
+    match rule {
+        FillRule::NonZero => "nonzero",
+        FillRule::EvenOdd => "evenodd",
+    }
+}
+
+fn color_attrs(attr: &str, color: Rgba) -> String {
+    let [r, g, b, _] = color::to_bytes(color);
+    format!(r#"{a}="rgb({},{},{})" {a}-opacity="{}""#, r, g, b, color.3, a = attr)
+}
+
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use canvas::Target;
+    use draw_list::DrawList;
+    use shape::rect;
+    use style::Stroke;
+
+    fn render<F: FnMut(&DrawList)>(mut draw: F) -> String {
+        let list = DrawList::new();
+        draw(&list);
+        let mut document = SvgDocument::new(20, 20);
+        assert!(document.draw_all(list.into_commands()));
+        document.to_string()
+    }
+
+    #[test]
+    fn erasing_operations_leave_shapes_out() {
+        let svg = render(|t| {
+            t.draw(Transform::identity(), Style::fill((255, 0, 0)), rect(0.0, 0.0, 5.0, 5.0));
+            t.with_composite(CompositeOp::DestinationOut, |t| {
+                t.draw(rect(0.0, 0.0, 2.0, 2.0));
+                t.with_opacity_group(0.5, |t| t.draw(rect(1.0, 1.0, 2.0, 2.0)));
+            });
+            t.with_composite(CompositeOp::Multiply, |t| t.draw(rect(0.0, 0.0, 3.0, 3.0)));
+        });
+        assert_eq!(svg.matches("<rect").count(), 3);
+        assert!(svg.contains(r#"<g opacity="0.5" display="none">"#));
+        assert!(svg.contains("mix-blend-mode:multiply"));
+    }
+
+    #[test]
+    fn clearing_a_group_keeps_what_was_drawn_before_it() {
+        let svg = render(|t| {
+            t.draw(Transform::identity(), Style::fill((255, 0, 0)), rect(0.0, 0.0, 5.0, 5.0));
+            t.with_opacity_group(0.5, |t| {
+                t.draw(rect(0.0, 0.0, 2.0, 2.0));
+                t.with_clip(rect(0.0, 0.0, 3.0, 3.0), |t| t.clear((0, 0, 255)));
+            });
+        });
+        assert!(svg.contains(r#"width="5""#));
+        assert!(!svg.contains(r#"width="2""#));
+        // the clear is not clipped, but the clip is opened again after it
+        let group = svg.find(r#"<g opacity="0.5">"#).unwrap();
+        let clear = svg.find(r#"<rect x="-10" y="-10""#).unwrap();
+        let clip = svg.find("<g clip-path=").unwrap();
+        assert!(group < clear && clear < clip);
+        assert_eq!(svg.matches("<g clip-path=").count(), 1);
+    }
+
+    #[test]
+    fn stroke_clips_use_the_stroke_outline() {
+        let clip = |style: Style| {
+            let clip = rect(-5.0, -5.0, 10.0, 10.0).with_state(Transform::identity(), style);
+            let mut document = SvgDocument::new(20, 20);
+            document.draw_all(vec![DrawCmd::PushClip(Box::new(clip)), DrawCmd::PopClip {}]);
+            let svg = document.to_string();
+            let start = svg.find("<clipPath").unwrap();
+            svg[start..].to_string()
+        };
+        let stroked = clip(Style::stroke_with((0, 0, 0), Stroke::new(2.0)));
+        assert!(stroked.contains(r#"<path d="M "#));
+        assert!(!stroked.contains("<rect"));
+        assert!(clip(Style::fill((0, 0, 0))).contains("<rect"));
+    }
+
+    #[test]
+    fn focal_radii_are_only_written_when_needed() {
+        let gradient = |r0| {
+            let paint = Gradient::radial(0.0, 0.0, r0, 0.0, 0.0, 5.0)
+                .add_color_stop(0.0, (255, 0, 0))
+                .add_color_stop(1.0, (0, 0, 255));
+            render(|t| t.draw(Transform::identity(), Style::fill(paint.clone()),
+                rect(-5.0, -5.0, 10.0, 10.0)))
+        };
+        assert!(!gradient(0.0).contains("fr="));
+        assert!(gradient(1.0).contains(r#"fr="1""#));
+    }
+
+    #[test]
+    fn conic_gradients_become_image_patterns() {
+        let paint = Gradient::conic(0.0, 0.0, 0.0)
+            .add_color_stop(0.0, (255, 0, 0))
+            .add_color_stop(1.0, (0, 0, 255));
+        let svg = render(|t| t.draw(Transform::from_translation(2.0, 0.0),
+            Style::fill(paint.clone()), rect(-5.0, -5.0, 10.0, 10.0)));
+        assert!(svg.contains(r#"<pattern id="gradient0""#));
+        assert!(svg.contains("data:image/png;base64,"));
+        assert!(svg.contains(r#"fill="url(#gradient0)""#));
+    }
+}
@@ -0,0 +1,865 @@
+//! Converts shapes into flat polygon outlines that a backend can fill.
+//!
+//! Strokes are emitted as a set of positively wound pieces (one per segment and join) that are
+//! filled with the non-zero winding rule, so overlapping pieces never cancel or leave holes.
+
+use shape::{DrawCmd, Rect, Line, Circle, Arc, Path, Image, Text};
+use path::PathSegment;
+use style::{Style, FillRule, Stroke, LineCap, LineJoin};
+use transform::Transform;
+use paint::{Paint, Gradient, GradientSpace};
+
+use cgmath::Point2;
+
+use std::f32::consts::PI;
+
+/// Maximum distance, in canvas units, between a curve and its flattened polyline.
+pub const TOLERANCE: f32 = 0.25;
+
+/// A flattened polyline in shape-local coordinates.
+#[derive(Debug, Clone)]
+pub struct Subpath {
+    pub points: Vec<Point2<f32>>,
+    pub closed: bool,
+}
+
+/// A set of closed polygons in canvas coordinates.
+#[derive(Debug, Clone)]
+pub struct Outline {
+    pub contours: Vec<Vec<Point2<f32>>>,
+    pub fill_rule: FillRule,
+}
+
+impl Outline {
+    /// Returns the minimum and maximum corners of the outline, if it has any points.
+    pub fn bounds(&self) -> Option<(Point2<f32>, Point2<f32>)> {
+        let mut points = self.contours.iter().flat_map(|c| c.iter());
+        let first = match points.next() {
+            Some(p) => *p,
+            None => return None,
+        };
+        let (min, max) = points.fold((first, first), |(min, max), p| {
+            (Point2::new(min.x.min(p.x), min.y.min(p.y)),
+                Point2::new(max.x.max(p.x), max.y.max(p.y)))
+        });
+        Some((min, max))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contours.iter().all(|c| c.len() < 3)
+    }
+
+    /// Whether the outline is a single convex polygon, which a triangle fan covers exactly once
+    /// no matter the fill rule.
+    pub fn is_convex(&self) -> bool {
+        let contour = match self.contours.len() {
+            1 if self.contours[0].len() >= 3 => &self.contours[0],
+            _ => return false,
+        };
+        let n = contour.len();
+        let mut sign = 0.0;
+        let mut turning = 0.0;
+        for i in 0..n {
+            let (a, b, c) = (contour[i], contour[(i + 1) % n], contour[(i + 2) % n]);
+            let (u, v) = (b - a, c - b);
+            let cross = u.x * v.y - u.y * v.x;
+            let dot = u.x * v.x + u.y * v.y;
+            if cross == 0.0 {
+                // doubling back on itself is not convex; repeated points are fine
+                if dot < 0.0 {
+                    return false;
+                }
+                continue;
+            }
+            if sign * cross < 0.0 {
+                return false;
+            }
+            sign = cross.signum();
+            turning += cross.atan2(dot);
+        }
+        // a polygon that turns the same way throughout but winds around more than once is a star
+        turning.abs() < 2.0 * PI + 1e-3
+    }
+}
+
+/// Shapes that can be approximated by polylines.
+pub trait Flatten {
+    /// Approximates the shape so that no point strays more than `tolerance` from the true curve.
+    fn flatten(&self, tolerance: f32) -> Vec<Subpath>;
+
+    /// Whether the flattened shape has an interior when filled.
+    fn fillable(&self) -> bool {
+        true
+    }
+}
+
+impl Flatten for Rect {
+    fn flatten(&self, _tolerance: f32) -> Vec<Subpath> {
+        let (x, y, w, h) = (self.x, self.y, self.width, self.height);
+        vec![Subpath {
+            points: vec![
+                Point2::new(x, y),
+                Point2::new(x + w, y),
+                Point2::new(x + w, y + h),
+                Point2::new(x, y + h),
+            ],
+            closed: true,
+        }]
+    }
+}
+
+impl Flatten for Line {
+    fn flatten(&self, _tolerance: f32) -> Vec<Subpath> {
+        vec![Subpath {
+            points: vec![Point2::new(self.x1, self.y1), Point2::new(self.x2, self.y2)],
+            closed: false,
+        }]
+    }
+
+    fn fillable(&self) -> bool {
+        false
+    }
+}
+
+impl Flatten for Circle {
+    fn flatten(&self, tolerance: f32) -> Vec<Subpath> {
+        let center = Point2::new(self.x, self.y);
+        let mut points = arc_points(center, self.radius, 0.0, 2.0 * PI, tolerance);
+        // the last point duplicates the first
+        points.pop();
+        vec![Subpath { points, closed: true }]
+    }
+}
+
+impl Flatten for Arc {
+    fn flatten(&self, tolerance: f32) -> Vec<Subpath> {
+        let center = Point2::new(self.x, self.y);
+        let sweep = ccw_sweep(self.start_angle, self.end_angle);
+        vec![Subpath {
+            points: arc_points(center, self.radius, self.start_angle, sweep, tolerance),
+            closed: false,
+        }]
+    }
+}
+
+impl Flatten for Text {
+    fn flatten(&self, tolerance: f32) -> Vec<Subpath> {
+        self.outline().flatten(tolerance)
+    }
+
+    fn fillable(&self) -> bool {
+        !self.font.is_stroke_font()
+    }
+}
+
+impl Flatten for Path {
+    fn flatten(&self, tolerance: f32) -> Vec<Subpath> {
+        let mut builder = SubpathBuilder { subpaths: Vec::new(), current: Vec::new() };
+        for segment in &self.segments {
+            match *segment {
+                PathSegment::MoveTo { x, y } => builder.move_to(Point2::new(x, y)),
+                PathSegment::LineTo { x, y } => builder.line_to(Point2::new(x, y)),
+                PathSegment::QuadraticCurveTo { cpx, cpy, x, y } => {
+                    let ctrl = Point2::new(cpx, cpy);
+                    let from = builder.current_or(ctrl);
+                    let to = Point2::new(x, y);
+                    let n = quadratic_segments(from, ctrl, to, tolerance);
+                    for i in 1..n + 1 {
+                        builder.line_to(quadratic_point(from, ctrl, to, i as f32 / n as f32));
+                    }
+                }
+                PathSegment::BezierCurveTo { cp1x, cp1y, cp2x, cp2y, x, y } => {
+                    let (c1, c2) = (Point2::new(cp1x, cp1y), Point2::new(cp2x, cp2y));
+                    let from = builder.current_or(c1);
+                    let to = Point2::new(x, y);
+                    let n = cubic_segments(from, c1, c2, to, tolerance);
+                    for i in 1..n + 1 {
+                        builder.line_to(cubic_point(from, c1, c2, to, i as f32 / n as f32));
+                    }
+                }
+                PathSegment::Arc { x, y, radius, start_angle, end_angle, anticlockwise } => {
+                    let sweep = path_sweep(start_angle, end_angle, anticlockwise);
+                    let points = arc_points(Point2::new(x, y), radius, start_angle, sweep,
+                        tolerance);
+                    builder.extend(points);
+                }
+                PathSegment::ArcTo { x1, y1, x2, y2, radius } => {
+                    let corner = Point2::new(x1, y1);
+                    let from = builder.current_or(corner);
+                    match arc_to_geometry(from, corner, Point2::new(x2, y2), radius) {
+                        Some(arc) => {
+                            let points = arc_points(arc.center, radius, arc.start_angle,
+                                arc.sweep, tolerance);
+                            builder.extend(points);
+                        }
+                        None => builder.line_to(corner),
+                    }
+                }
+                PathSegment::Ellipse { x, y, radius_x, radius_y, rotation, start_angle,
+                        end_angle, anticlockwise } => {
+                    let sweep = path_sweep(start_angle, end_angle, anticlockwise);
+                    let segments = arc_segments(radius_x.abs().max(radius_y.abs()), sweep,
+                        tolerance);
+                    let (sin, cos) = rotation.sin_cos();
+                    let points = (0..segments + 1).map(|i| {
+                        let angle = start_angle + sweep * i as f32 / segments as f32;
+                        let (ex, ey) = (radius_x * angle.cos(), radius_y * angle.sin());
+                        Point2::new(x + ex * cos - ey * sin, y + ex * sin + ey * cos)
+                    }).collect();
+                    builder.extend(points);
+                }
+                PathSegment::ClosePath => builder.close(),
+            }
+        }
+        builder.finish()
+    }
+}
+
+/// Collects points into subpaths following the HTML5 Canvas rules for implicit subpaths.
+struct SubpathBuilder {
+    subpaths: Vec<Subpath>,
+    current: Vec<Point2<f32>>,
+}
+
+impl SubpathBuilder {
+    fn end_subpath(&mut self, closed: bool) {
+        let points = ::std::mem::take(&mut self.current);
+        if points.len() >= 2 {
+            self.subpaths.push(Subpath { points, closed });
+        }
+    }
+
+    fn move_to(&mut self, p: Point2<f32>) {
+        self.end_subpath(false);
+        self.current.push(p);
+    }
+
+    fn line_to(&mut self, p: Point2<f32>) {
+        self.current.push(p);
+    }
+
+    /// Connects the current subpath to the first point with a line, or starts a new subpath.
+    fn extend(&mut self, points: Vec<Point2<f32>>) {
+        self.current.extend(points);
+    }
+
+    /// The current point, first moving to `p` if there is no subpath yet.
+    fn current_or(&mut self, p: Point2<f32>) -> Point2<f32> {
+        match self.current.last() {
+            Some(last) => *last,
+            None => {
+                self.current.push(p);
+                p
+            }
+        }
+    }
+
+    /// Closes the current subpath; the next one starts where it began.
+    fn close(&mut self) {
+        let start = match self.current.first() {
+            Some(p) => *p,
+            None => return,
+        };
+        self.end_subpath(true);
+        self.current.push(start);
+    }
+
+    fn finish(mut self) -> Vec<Subpath> {
+        self.end_subpath(false);
+        self.subpaths
+    }
+}
+
+/// Sweep of a path arc. Positive sweeps go counter clockwise.
+///
+/// Like HTML5, angles a full turn or more apart draw a full circle in the requested direction.
+pub fn path_sweep(start: f32, end: f32, anticlockwise: bool) -> f32 {
+    if (end - start).abs() >= 2.0 * PI {
+        if anticlockwise { 2.0 * PI } else { -2.0 * PI }
+    } else if anticlockwise {
+        ccw_sweep(start, end)
+    } else {
+        -ccw_sweep(end, start)
+    }
+}
+
+/// The circular arc drawn by `PathSegment::ArcTo`.
+pub struct ArcToGeometry {
+    pub center: Point2<f32>,
+    /// The point where the arc touches the line from the current point to the corner.
+    pub start: Point2<f32>,
+    /// The point where the arc touches the line leaving the corner.
+    pub end: Point2<f32>,
+    pub start_angle: f32,
+    pub sweep: f32,
+}
+
+/// Finds the arc of `radius` tangent to the lines `from`-`corner` and `corner`-`to`.
+///
+/// Returns `None` when the arc degenerates into a straight line to `corner`.
+pub fn arc_to_geometry(from: Point2<f32>, corner: Point2<f32>, to: Point2<f32>, radius: f32)
+        -> Option<ArcToGeometry> {
+    if from == corner || corner == to || radius == 0.0 {
+        return None;
+    }
+    let v1 = normalize(sub(from, corner));
+    let v2 = normalize(sub(to, corner));
+    if cross(v1, v2).abs() <= 1e-6 {
+        return None;
+    }
+    // angle between the two lines at the corner
+    let half = dot(v1, v2).clamp(-1.0, 1.0).acos() / 2.0;
+    let tangent_dist = radius / half.tan();
+    let center_dist = radius / half.sin();
+    let bisector = normalize((v1.0 + v2.0, v1.1 + v2.1));
+    let center = offset(corner, (bisector.0 * center_dist, bisector.1 * center_dist));
+    let start = offset(corner, (v1.0 * tangent_dist, v1.1 * tangent_dist));
+    let end = offset(corner, (v2.0 * tangent_dist, v2.1 * tangent_dist));
+    let start_angle = (start.y - center.y).atan2(start.x - center.x);
+    let end_angle = (end.y - center.y).atan2(end.x - center.x);
+    // the arc always takes the short way around
+    let mut sweep = end_angle - start_angle;
+    if sweep > PI {
+        sweep -= 2.0 * PI;
+    } else if sweep < -PI {
+        sweep += 2.0 * PI;
+    }
+    Some(ArcToGeometry { center, start, end, start_angle, sweep })
+}
+
+fn quadratic_point(p0: Point2<f32>, p1: Point2<f32>, p2: Point2<f32>, t: f32) -> Point2<f32> {
+    let u = 1.0 - t;
+    Point2::new(u * u * p0.x + 2.0 * u * t * p1.x + t * t * p2.x,
+        u * u * p0.y + 2.0 * u * t * p1.y + t * t * p2.y)
+}
+
+fn cubic_point(p0: Point2<f32>, p1: Point2<f32>, p2: Point2<f32>, p3: Point2<f32>, t: f32)
+        -> Point2<f32> {
+    let u = 1.0 - t;
+    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+    Point2::new(a * p0.x + b * p1.x + c * p2.x + d * p3.x,
+        a * p0.y + b * p1.y + c * p2.y + d * p3.y)
+}
+
+/// Length of the second difference `a - 2b + c`, which bounds a Bézier curve's curvature.
+fn second_difference(a: Point2<f32>, b: Point2<f32>, c: Point2<f32>) -> f32 {
+    let (x, y) = (a.x - 2.0 * b.x + c.x, a.y - 2.0 * b.y + c.y);
+    (x * x + y * y).sqrt()
+}
+
+fn quadratic_segments(p0: Point2<f32>, p1: Point2<f32>, p2: Point2<f32>, tolerance: f32)
+        -> usize {
+    let dd = second_difference(p0, p1, p2);
+    ((dd / (4.0 * tolerance)).sqrt().ceil() as usize).clamp(1, 1024)
+}
+
+fn cubic_segments(p0: Point2<f32>, p1: Point2<f32>, p2: Point2<f32>, p3: Point2<f32>,
+        tolerance: f32) -> usize {
+    let dd = second_difference(p0, p1, p2).max(second_difference(p1, p2, p3));
+    ((3.0 * dd / (4.0 * tolerance)).sqrt().ceil() as usize).clamp(1, 1024)
+}
+
+/// Returns the counter clockwise sweep from `start` to `end`, between 0 and a full turn.
+pub fn ccw_sweep(start: f32, end: f32) -> f32 {
+    let sweep = end - start;
+    if sweep >= 2.0 * PI {
+        2.0 * PI
+    } else if sweep < 0.0 {
+        let sweep = sweep % (2.0 * PI);
+        if sweep < 0.0 { sweep + 2.0 * PI } else { sweep }
+    } else {
+        sweep
+    }
+}
+
+/// Number of segments needed to approximate a `sweep` radian arc of `radius` within `tolerance`.
+pub fn arc_segments(radius: f32, sweep: f32, tolerance: f32) -> usize {
+    let radius = radius.abs();
+    if radius <= tolerance {
+        return 1.max((sweep.abs() / (PI / 2.0)).ceil() as usize);
+    }
+    let step = 2.0 * (1.0 - tolerance / radius).acos();
+    ((sweep.abs() / step).ceil() as usize).clamp(1, 1024)
+}
+
+/// Points along an arc, including both end points. A negative sweep goes clockwise.
+pub fn arc_points(center: Point2<f32>, radius: f32, start: f32, sweep: f32, tolerance: f32)
+        -> Vec<Point2<f32>> {
+    let segments = arc_segments(radius, sweep, tolerance);
+    (0..segments + 1)
+        .map(|i| {
+            let angle = start + sweep * i as f32 / segments as f32;
+            Point2::new(center.x + radius * angle.cos(), center.y + radius * angle.sin())
+        })
+        .collect()
+}
+
+/// The largest factor by which `transform` stretches lengths.
+pub fn scale_factor(transform: &Transform) -> f32 {
+    // the largest singular value of the linear part
+    let (a, b, c, d) = (transform.a, transform.b, transform.c, transform.d);
+    let sum = a * a + b * b + c * c + d * d;
+    let det = a * d - b * c;
+    ((sum + (sum * sum - 4.0 * det * det).max(0.0).sqrt()) / 2.0).sqrt()
+}
+
+/// The flattening tolerance, in shape-local units, that keeps errors within `TOLERANCE` on the
+/// canvas.
+pub fn tolerance_for(transform: &Transform) -> f32 {
+    let scale = scale_factor(transform);
+    if scale > 0.0 { TOLERANCE / scale } else { TOLERANCE }
+}
+
+/// Flatten `shape` precisely enough to be drawn under `transform`.
+pub fn flatten_for<S: Flatten>(shape: &S, transform: &Transform) -> Vec<Subpath> {
+    shape.flatten(tolerance_for(transform))
+}
+
+/// The outline covered by filling `subpaths`. Open subpaths are closed implicitly.
+pub fn fill(subpaths: &[Subpath], rule: FillRule, transform: &Transform) -> Outline {
+    let contours = subpaths.iter()
+        .filter(|s| s.points.len() >= 3)
+        .map(|s| s.points.iter().map(|p| transform.transform_point(*p)).collect())
+        .collect();
+    Outline { contours, fill_rule: rule }
+}
+
+/// The outline covered by stroking `subpaths`.
+pub fn stroke(subpaths: &[Subpath], stroke: &Stroke, transform: &Transform) -> Outline {
+    let mut stroker = Stroker {
+        half_width: stroke.width / 2.0,
+        cap: stroke.cap,
+        join: stroke.join,
+        miter_limit: stroke.miter_limit,
+        tolerance: tolerance_for(transform),
+        pieces: Vec::new(),
+    };
+    for subpath in subpaths {
+        if stroke.dash.is_solid() {
+            stroker.stroke(subpath);
+        } else {
+            for dash in dash_subpath(subpath, stroke.dash.lengths(), stroke.dash_offset) {
+                stroker.stroke(&dash);
+            }
+        }
+    }
+    let contours = stroker.pieces.into_iter()
+        .map(|piece| piece.into_iter().map(|p| transform.transform_point(p)).collect())
+        .collect();
+    Outline { contours, fill_rule: FillRule::NonZero }
+}
+
+/// Tessellates a shape according to its style.
+pub fn shape_outline<S: Flatten>(shape: &S, style: &Style, transform: &Transform) -> Outline {
+    let subpaths = flatten_for(shape, transform);
+    match *style {
+        Style::FillStyle { rule, .. } => {
+            if shape.fillable() {
+                fill(&subpaths, rule, transform)
+            } else {
+                Outline { contours: Vec::new(), fill_rule: rule }
+            }
+        }
+        Style::StrokeStyle { ref stroke, .. } => self::stroke(&subpaths, stroke, transform),
+    }
+}
+
+/// The outline of the destination rectangle of an image.
+pub fn image_outline(image: &Image, transform: &Transform) -> Outline {
+    fill(&flatten_for(&image.dest, transform), FillRule::NonZero, transform)
+}
+
+/// The area a `DrawCmd::PushClip` restricts drawing to.
+///
+/// Shapes clip to the area they would cover when drawn, images to their destination rectangle.
+/// Commands that draw nothing clip everything away.
+pub fn clip_outline(cmd: &DrawCmd) -> Outline {
+    match *cmd {
+        DrawCmd::Image { ref image, ref transform, .. } => image_outline(image, transform),
+        ref cmd => match tessellate(cmd) {
+            Some(tessellation) => tessellation.outline,
+            None => Outline { contours: Vec::new(), fill_rule: FillRule::NonZero },
+        },
+    }
+}
+
+/// A shape that has been tessellated and is ready to be filled.
+#[derive(Debug, Clone)]
+pub struct Tessellation {
+    pub outline: Outline,
+    pub paint: Paint,
+    /// Maps the paint's coordinates to canvas coordinates.
+    pub paint_transform: Transform,
+}
+
+/// Tessellates a `DrawCmd`, returning `None` if the command does not draw a shape.
+///
+/// Images are not painted shapes and also return `None`; see `image_outline`.
+pub fn tessellate(cmd: &DrawCmd) -> Option<Tessellation> {
+    let (outline, style, transform) = match *cmd {
+        DrawCmd::Rect { ref rect, ref style, ref transform } =>
+            (shape_outline(rect, style, transform), style, transform),
+        DrawCmd::Line { ref line, ref style, ref transform } =>
+            (shape_outline(line, style, transform), style, transform),
+        DrawCmd::Circle { ref circle, ref style, ref transform } =>
+            (shape_outline(circle, style, transform), style, transform),
+        DrawCmd::Arc { ref arc, ref style, ref transform } =>
+            (shape_outline(arc, style, transform), style, transform),
+        DrawCmd::Path { ref path, ref style, ref transform } =>
+            (shape_outline(path, style, transform), style, transform),
+        DrawCmd::Text { ref text, ref style, ref transform } =>
+            (shape_outline(text, style, transform), style, transform),
+        _ => return None,
+    };
+    let paint = style.paint().clone();
+    let paint_transform = paint_transform(&paint, transform);
+    Some(Tessellation { outline, paint, paint_transform })
+}
+
+/// Maps the coordinates of a paint used in a draw with `transform` to canvas coordinates.
+pub fn paint_transform(paint: &Paint, transform: &Transform) -> Transform {
+    match *paint {
+        Paint::Gradient(Gradient { space: GradientSpace::Canvas, .. }) => Transform::identity(),
+        _ => *transform,
+    }
+}
+
+fn sub(a: Point2<f32>, b: Point2<f32>) -> (f32, f32) {
+    (a.x - b.x, a.y - b.y)
+}
+
+fn offset(p: Point2<f32>, (dx, dy): (f32, f32)) -> Point2<f32> {
+    Point2::new(p.x + dx, p.y + dy)
+}
+
+fn cross(a: (f32, f32), b: (f32, f32)) -> f32 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn dot(a: (f32, f32), b: (f32, f32)) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn normalize(v: (f32, f32)) -> (f32, f32) {
+    let len = dot(v, v).sqrt();
+    (v.0 / len, v.1 / len)
+}
+
+/// Twice the signed area of a polygon; positive when it winds counter clockwise.
+fn signed_area(points: &[Point2<f32>]) -> f32 {
+    let n = points.len();
+    (0..n).map(|i| {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        a.x * b.y - b.x * a.y
+    }).sum()
+}
+
+/// Splits a subpath into the open subpaths that make up its dashes.
+///
+/// Invalid patterns, i.e. ones with negative lengths or that add up to zero, leave the subpath
+/// solid.
+pub fn dash_subpath(subpath: &Subpath, pattern: &[f32], dash_offset: f32) -> Vec<Subpath> {
+    let mut pattern = pattern.to_vec();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_slice(&pattern.clone());
+    }
+    let total: f32 = pattern.iter().sum();
+    if pattern.iter().any(|len| len.is_nan() || *len < 0.0 || len.is_infinite())
+            || total.is_nan() || total <= 0.0 {
+        return vec![subpath.clone()];
+    }
+
+    // find where in the pattern the subpath starts
+    let mut phase = dash_offset % total;
+    if phase < 0.0 {
+        phase += total;
+    }
+    let mut index = 0;
+    while phase >= pattern[index] {
+        phase -= pattern[index];
+        index = (index + 1) % pattern.len();
+    }
+    let mut remaining = pattern[index] - phase;
+
+    let mut points = subpath.points.clone();
+    if subpath.closed && !points.is_empty() {
+        let first = points[0];
+        points.push(first);
+    }
+    let mut dashes = Vec::new();
+    let mut current = Vec::new();
+    if index % 2 == 0 && !points.is_empty() {
+        current.push(points[0]);
+    }
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let len = dot(sub(b, a), sub(b, a)).sqrt();
+        if len == 0.0 {
+            continue;
+        }
+        let dir = ((b.x - a.x) / len, (b.y - a.y) / len);
+        let at = |dist: f32| offset(a, (dir.0 * dist, dir.1 * dist));
+        let mut pos = 0.0;
+        loop {
+            let step = remaining.min(len - pos);
+            pos += step;
+            remaining -= step;
+            if remaining > 0.0 {
+                if index % 2 == 0 {
+                    current.push(b);
+                }
+                break;
+            }
+            // the current dash or gap ends on this segment
+            if index % 2 == 0 {
+                let end = at(pos);
+                if current.len() == 1 && current[0] == end {
+                    // a zero length dash still needs a direction for its caps
+                    current.push(offset(end, (dir.0 * 1e-4, dir.1 * 1e-4)));
+                } else {
+                    current.push(end);
+                }
+                dashes.push(Subpath {
+                    points: ::std::mem::take(&mut current),
+                    closed: false,
+                });
+            }
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+            if index % 2 == 0 {
+                current.push(at(pos));
+            }
+            if pos >= len && remaining > 0.0 {
+                break;
+            }
+        }
+    }
+    if current.len() >= 2 {
+        dashes.push(Subpath { points: current, closed: false });
+    }
+    dashes
+}
+
+struct Stroker {
+    half_width: f32,
+    cap: LineCap,
+    join: LineJoin,
+    miter_limit: f32,
+    tolerance: f32,
+    pieces: Vec<Vec<Point2<f32>>>,
+}
+
+impl Stroker {
+    /// Adds a convex piece of the stroke, making sure it winds counter clockwise.
+    fn push_piece(&mut self, mut piece: Vec<Point2<f32>>) {
+        let area = signed_area(&piece);
+        if area.abs() <= f32::EPSILON {
+            return;
+        }
+        if area < 0.0 {
+            piece.reverse();
+        }
+        self.pieces.push(piece);
+    }
+
+    fn stroke(&mut self, subpath: &Subpath) {
+        let mut points = subpath.points.clone();
+        points.dedup();
+        if subpath.closed && points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        match points.len() {
+            0 => return,
+            1 => {
+                // a zero length subpath only shows up through its caps
+                if !subpath.closed {
+                    self.cap(points[0], (1.0, 0.0));
+                    self.cap(points[0], (-1.0, 0.0));
+                }
+                return;
+            }
+            _ => {}
+        }
+        let n = points.len();
+        let segments = if subpath.closed { n } else { n - 1 };
+        for i in 0..segments {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            let dir = normalize(sub(b, a));
+            let normal = (-dir.1 * self.half_width, dir.0 * self.half_width);
+            let anti = (-normal.0, -normal.1);
+            self.push_piece(vec![offset(a, normal), offset(b, normal), offset(b, anti),
+                offset(a, anti)]);
+        }
+        let joins = if subpath.closed { 0..n } else { 1..n - 1 };
+        for i in joins {
+            let prev = points[(i + n - 1) % n];
+            let next = points[(i + 1) % n];
+            self.join(prev, points[i], next);
+        }
+        if !subpath.closed {
+            self.cap(points[0], normalize(sub(points[0], points[1])));
+            self.cap(points[n - 1], normalize(sub(points[n - 1], points[n - 2])));
+        }
+    }
+
+    /// Adds a cap to the end point `p` of a line, where `dir` points away from the line.
+    fn cap(&mut self, p: Point2<f32>, dir: (f32, f32)) {
+        let hw = self.half_width;
+        let normal = (-dir.1 * hw, dir.0 * hw);
+        let anti = (-normal.0, -normal.1);
+        let ahead = (dir.0 * hw, dir.1 * hw);
+        match self.cap {
+            LineCap::Butt => {}
+            LineCap::Square => {
+                self.push_piece(vec![offset(p, normal), offset(offset(p, normal), ahead),
+                    offset(offset(p, anti), ahead), offset(p, anti)]);
+            }
+            LineCap::Round => {
+                // sweep from the left hand side of the line through `dir` to the right
+                let start = normal.1.atan2(normal.0);
+                let points = arc_points(p, hw, start, -PI, self.tolerance);
+                self.push_piece(points);
+            }
+        }
+    }
+
+    /// Fills the gap on the outside of the corner at `p`.
+    fn join(&mut self, prev: Point2<f32>, p: Point2<f32>, next: Point2<f32>) {
+        let d0 = normalize(sub(p, prev));
+        let d1 = normalize(sub(next, p));
+        let turn = cross(d0, d1);
+        if turn.abs() <= 1e-6 {
+            if dot(d0, d1) < 0.0 && self.join == LineJoin::Round {
+                // the line doubles back on itself, which leaves a round end behind
+                self.push_piece(arc_points(p, self.half_width, d0.0.atan2(-d0.1), -PI,
+                    self.tolerance));
+            }
+            return;
+        }
+        // the outside of a left turn is on the right hand side
+        let side = if turn > 0.0 { -self.half_width } else { self.half_width };
+        let o0 = (-d0.1 * side, d0.0 * side);
+        let o1 = (-d1.1 * side, d1.0 * side);
+        let (a, b) = (offset(p, o0), offset(p, o1));
+
+        match self.join {
+            LineJoin::Miter => {
+                // the miter tip lies along the bisector of the two offsets
+                let cos_theta = dot(d0, d1).clamp(-1.0, 1.0);
+                let miter_ratio = (2.0 / (1.0 + cos_theta)).sqrt();
+                if miter_ratio <= self.miter_limit {
+                    let bisector = normalize((o0.0 + o1.0, o0.1 + o1.1));
+                    let len = self.half_width * miter_ratio;
+                    let tip = offset(p, (bisector.0 * len, bisector.1 * len));
+                    self.push_piece(vec![p, a, tip, b]);
+                } else {
+                    self.push_piece(vec![p, a, b]);
+                }
+            }
+            LineJoin::Bevel => {
+                self.push_piece(vec![p, a, b]);
+            }
+            LineJoin::Round => {
+                let start = o0.1.atan2(o0.0);
+                let sweep = turn.atan2(dot(d0, d1));
+                let mut piece = vec![p];
+                piece.extend(arc_points(p, self.half_width, start, sweep, self.tolerance));
+                self.push_piece(piece);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_circle_path_arcs_are_not_empty() {
+        let identity = Transform::identity();
+        for &(start, end, anticlockwise) in &[(0.0, 2.0 * PI, false), (0.0, -2.0 * PI, true),
+                (0.0, 2.0 * PI, true), (2.0 * PI, 0.0, false)] {
+            let sweep = path_sweep(start, end, anticlockwise);
+            assert_eq!(sweep.abs(), 2.0 * PI);
+            assert_eq!(sweep > 0.0, anticlockwise);
+            let path = Path::new().arc(0.0, 0.0, 10.0, start, end, anticlockwise);
+            let outline = fill(&flatten_for(&path, &identity), FillRule::NonZero, &identity);
+            assert!(!outline.is_empty());
+            let (min, max) = outline.bounds().unwrap();
+            assert!(min.x < -9.5 && min.y < -9.5 && max.x > 9.5 && max.y > 9.5);
+        }
+    }
+
+    #[test]
+    fn partial_path_arcs_wrap_around() {
+        assert!((path_sweep(0.0, PI / 2.0, true) - PI / 2.0).abs() < 1e-6);
+        assert!((path_sweep(0.0, PI / 2.0, false) + 3.0 * PI / 2.0).abs() < 1e-6);
+        assert!((path_sweep(PI / 2.0, 0.0, false) + PI / 2.0).abs() < 1e-6);
+    }
+
+    fn line(points: &[(f32, f32)]) -> Subpath {
+        Subpath { points: points.iter().map(|&(x, y)| Point2::new(x, y)).collect(), closed: false }
+    }
+
+    fn dash_points(subpath: &Subpath, pattern: &[f32], dash_offset: f32) -> Vec<Vec<(f32, f32)>> {
+        dash_subpath(subpath, pattern, dash_offset).iter()
+            .map(|dash| dash.points.iter().map(|p| (p.x, p.y)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn dashes_split_subpaths() {
+        let straight = line(&[(0.0, 0.0), (10.0, 0.0)]);
+        assert_eq!(dash_points(&straight, &[4.0, 2.0], 0.0),
+            vec![vec![(0.0, 0.0), (4.0, 0.0)], vec![(6.0, 0.0), (10.0, 0.0)]]);
+        // odd patterns are repeated, so dashes and gaps alternate
+        assert_eq!(dash_points(&straight, &[3.0], 0.0),
+            vec![vec![(0.0, 0.0), (3.0, 0.0)], vec![(6.0, 0.0), (9.0, 0.0)]]);
+        // invalid patterns leave the subpath solid
+        for pattern in &[&[1.0, -1.0][..], &[0.0, 0.0], &[f32::NAN, 1.0]] {
+            assert_eq!(dash_points(&straight, pattern, 0.0), vec![vec![(0.0, 0.0), (10.0, 0.0)]]);
+        }
+    }
+
+    #[test]
+    fn dash_offsets_wrap_around_the_pattern() {
+        let straight = line(&[(0.0, 0.0), (10.0, 0.0)]);
+        let expected = vec![vec![(0.0, 0.0), (3.0, 0.0)], vec![(5.0, 0.0), (9.0, 0.0)]];
+        for &offset in &[1.0, 7.0, 13.0, -5.0] {
+            assert_eq!(dash_points(&straight, &[4.0, 2.0], offset), expected, "offset {}", offset);
+        }
+    }
+
+    #[test]
+    fn dashes_follow_corners_and_curves() {
+        let corner = line(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)]);
+        assert_eq!(dash_points(&corner, &[6.0, 10.0], 0.0),
+            vec![vec![(0.0, 0.0), (4.0, 0.0), (4.0, 2.0)]]);
+
+        let half_circle = Path::new().arc(0.0, 0.0, 10.0, 0.0, PI, true);
+        let subpaths = flatten_for(&half_circle, &Transform::identity());
+        let dashes = dash_subpath(&subpaths[0], &[5.0 * PI, 100.0], 0.0);
+        assert_eq!(dashes.len(), 1);
+        for p in &dashes[0].points {
+            assert!(((p.x * p.x + p.y * p.y).sqrt() - 10.0).abs() < 0.5);
+        }
+        // the dash ends a quarter turn around the circle
+        let end = *dashes[0].points.last().unwrap();
+        assert!(end.x.abs() < 0.5 && (end.y - 10.0).abs() < 0.5, "{:?}", end);
+    }
+
+    #[test]
+    fn dashes_restart_on_every_subpath() {
+        let subpaths = [line(&[(0.0, 0.0), (10.0, 0.0)]), line(&[(0.0, 5.0), (10.0, 5.0)])];
+        let outline = stroke(&subpaths, &Stroke::new(2.0).dash(&[4.0, 2.0]),
+            &Transform::identity());
+        // both lines start with a dash at x = 0
+        for &y in &[0.0, 5.0] {
+            let min_x = outline.contours.iter().flat_map(|c| c.iter())
+                .filter(|p| (p.y - y).abs() <= 1.0)
+                .map(|p| p.x)
+                .fold(f32::INFINITY, f32::min);
+            assert!(min_x.abs() < 1e-4, "line at {} starts at {}", y, min_x);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Images that can be drawn onto a canvas.
+
+use color::Rgba;
+
+use image::{self, ImageError};
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// An RGBA8 image that can be drawn with the `image` shape.
+///
+/// Cloning a texture is cheap; clones share the same pixels. Hosts use the texture's id to upload
+/// its pixels only once, so the pixels cannot be changed after the texture is created.
+#[derive(Clone)]
+pub struct Texture {
+    data: Arc<TextureData>,
+}
+
+struct TextureData {
+    id: usize,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Texture {
+    /// Creates a texture from RGBA8 pixels, top row first.
+    ///
+    /// Panics if `pixels` does not hold exactly `width * height` pixels.
+    pub fn from_rgba(width: u32, height: u32, pixels: Vec<u8>) -> Texture {
+        assert_eq!(pixels.len(), width as usize * height as usize * 4,
+            "pixel buffer does not match the texture dimensions");
+        Texture {
+            data: Arc::new(TextureData {
+                id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+                width,
+                height,
+                pixels,
+            }),
+        }
+    }
+
+    /// Decodes a PNG, JPEG or BMP image.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Texture> {
+        let decoded = image::load_from_memory(bytes).map_err(to_io_error)?;
+        Ok(Texture::from_image(decoded))
+    }
+
+    /// Loads a PNG, JPEG or BMP image from a file. The format is guessed from the file extension.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Texture> {
+        let decoded = image::open(path).map_err(to_io_error)?;
+        Ok(Texture::from_image(decoded))
+    }
+
+    fn from_image(decoded: image::DynamicImage) -> Texture {
+        let rgba = decoded.to_rgba();
+        let (width, height) = rgba.dimensions();
+        Texture::from_rgba(width, height, rgba.into_raw())
+    }
+
+    /// A number that no other texture created by this process shares.
+    pub fn id(&self) -> usize {
+        self.data.id
+    }
+
+    pub fn width(&self) -> u32 {
+        self.data.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.data.height
+    }
+
+    /// The RGBA8 pixel data, top row first.
+    pub fn pixels(&self) -> &[u8] {
+        &self.data.pixels
+    }
+
+    /// Returns the pixel in column `x` and row `y`, counting from the top left corner.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.data.width as usize + x as usize) * 4;
+        let p = &self.data.pixels;
+        [p[i], p[i + 1], p[i + 2], p[i + 3]]
+    }
+
+    /// Samples the texture at `(x, y)` in pixels from the top left corner, where pixel centers
+    /// are at half pixels. Points outside the texture take the color of the nearest edge pixel.
+    ///
+    /// With `smoothing` the four nearest pixels are interpolated, otherwise the nearest pixel is
+    /// used.
+    pub fn sample(&self, x: f32, y: f32, smoothing: bool) -> Rgba {
+        let (w, h) = (self.data.width as i64, self.data.height as i64);
+        if w == 0 || h == 0 {
+            return (0.0, 0.0, 0.0, 0.0);
+        }
+        let texel = |x: i64, y: i64| {
+            let p = self.pixel(x.max(0).min(w - 1) as u32, y.max(0).min(h - 1) as u32);
+            [p[0] as f32, p[1] as f32, p[2] as f32, p[3] as f32]
+        };
+        let c = if smoothing {
+            let (fx, fy) = (x - 0.5, y - 0.5);
+            let (x0, y0) = (fx.floor(), fy.floor());
+            let (ax, ay) = (fx - x0, fy - y0);
+            let (x0, y0) = (x0 as i64, y0 as i64);
+            let (c00, c10) = (texel(x0, y0), texel(x0 + 1, y0));
+            let (c01, c11) = (texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+            let mut c = [0.0; 4];
+            for i in 0..4 {
+                let top = c00[i] + (c10[i] - c00[i]) * ax;
+                let bottom = c01[i] + (c11[i] - c01[i]) * ax;
+                c[i] = top + (bottom - top) * ay;
+            }
+            c
+        } else {
+            texel(x.floor() as i64, y.floor() as i64)
+        };
+        (c[0] / 255.0, c[1] / 255.0, c[2] / 255.0, c[3] / 255.0)
+    }
+
+    /// Whether this is the only handle to the texture's pixels.
+    ///
+    /// Hosts that cache uploaded textures use this to find textures nobody can draw anymore.
+    #[cfg(feature = "drawing")]
+    pub(crate) fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.data) == 1
+    }
+}
+
+impl fmt::Debug for Texture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Texture")
+            .field("id", &self.data.id)
+            .field("width", &self.data.width)
+            .field("height", &self.data.height)
+            .finish()
+    }
+}
+
+/// Textures are equal if they share the same pixels, i.e. one is a clone of the other.
+impl PartialEq for Texture {
+    fn eq(&self, other: &Texture) -> bool {
+        self.data.id == other.data.id
+    }
+}
+
+fn to_io_error(err: ImageError) -> io::Error {
+    match err {
+        ImageError::IoError(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
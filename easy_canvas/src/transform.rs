@@ -1,25 +1,208 @@
 use cgmath::prelude::*;
-use cgmath::{Decomposed, Basis2, Rotation2, Vector2, vec2, Transform as CgMathTransformTrait, Rad};
+use cgmath::{Basis2, Rotation2, Vector2, Point2, Matrix3, Matrix4, Rad};
 
-#[derive(Debug, Clone, Copy)]
+use std::ops::Mul;
+
+/// A 2D affine transform: any combination of translation, rotation, scale and skew.
+///
+/// Like `setTransform(a, b, c, d, e, f)` in HTML5 Canvas, a point `(x, y)` is mapped to
+/// `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Transform {
-    decomp: Decomposed<Vector2<f32>, Basis2<f32>>
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
 }
 
 impl Transform {
     pub fn identity() -> Transform {
-        Transform {
-            decomp: Decomposed::one()
-        }
+        Transform::from_matrix(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    /// A transform from the six entries of its matrix, in the order HTML5 Canvas uses for
+    /// `setTransform`.
+    pub fn from_matrix(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Transform {
+        Transform { a, b, c, d, e, f }
     }
 
+    /// A counter clockwise rotation around the origin.
     pub fn from_rot<A: Angle + Into<Rad<f32>>>(angle: A) -> Transform {
-        Transform {
-            decomp: Decomposed {
-                scale: 1.0,
-                disp: vec2(0.0 , 0.0),
-                rot: Rotation2::from_angle(angle)
+        let rot: Basis2<f32> = Rotation2::from_angle(angle);
+        let x = rot.rotate_vector(Vector2::unit_x());
+        let y = rot.rotate_vector(Vector2::unit_y());
+        Transform::from_matrix(x.x, x.y, y.x, y.y, 0.0, 0.0)
+    }
+
+    pub fn from_translation(x: f32, y: f32) -> Transform {
+        Transform::from_matrix(1.0, 0.0, 0.0, 1.0, x, y)
+    }
+
+    /// Scales both axes by `scale`, away from the origin.
+    pub fn from_scale(scale: f32) -> Transform {
+        Transform::from_nonuniform_scale(scale, scale)
+    }
+
+    /// Scales the x-axis by `x` and the y-axis by `y`, away from the origin.
+    pub fn from_nonuniform_scale(x: f32, y: f32) -> Transform {
+        Transform::from_matrix(x, 0.0, 0.0, y, 0.0, 0.0)
+    }
+
+    /// Slants the y-axis towards the x-axis by `x` and the x-axis towards the y-axis by `y`, so a
+    /// skew of `(Deg(30.0), Deg(0.0))` turns squares into parallelograms leaning to the right.
+    pub fn from_skew<A: Angle + Into<Rad<f32>>>(x: A, y: A) -> Transform {
+        let (x, y): (Rad<f32>, Rad<f32>) = (x.into(), y.into());
+        Transform::from_matrix(1.0, y.0.tan(), x.0.tan(), 1.0, 0.0, 0.0)
+    }
+
+    /// A transform that applies this transform and then `next`.
+    ///
+    /// This is `next * self`; e.g. `Transform::from_rot(angle).then(Transform::from_translation(x,
+    /// y))` rotates a shape around its own origin and then moves it to `(x, y)`.
+    pub fn then(&self, next: Transform) -> Transform {
+        Transform::from_matrix(
+            next.a * self.a + next.c * self.b,
+            next.b * self.a + next.d * self.b,
+            next.a * self.c + next.c * self.d,
+            next.b * self.c + next.d * self.d,
+            next.a * self.e + next.c * self.f + next.e,
+            next.b * self.e + next.d * self.f + next.f,
+        )
+    }
+
+    /// The transform that undoes this one, or `None` if this transform squashes the plane onto a
+    /// line or a point.
+    pub fn invert(&self) -> Option<Transform> {
+        let det = self.a * self.d - self.b * self.c;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let (a, b, c, d) = (self.d / det, -self.b / det, -self.c / det, self.a / det);
+        Some(Transform::from_matrix(a, b, c, d,
+            -(a * self.e + c * self.f), -(b * self.e + d * self.f)))
+    }
+
+    /// Maps a point from shape-local coordinates to canvas coordinates.
+    pub fn transform_point(&self, point: Point2<f32>) -> Point2<f32> {
+        Point2::new(self.a * point.x + self.c * point.y + self.e,
+            self.b * point.x + self.d * point.y + self.f)
+    }
+
+    /// Maps a direction or a distance from shape-local coordinates to canvas coordinates, which
+    /// ignores the translation.
+    pub fn transform_vector(&self, vector: Vector2<f32>) -> Vector2<f32> {
+        Vector2::new(self.a * vector.x + self.c * vector.y, self.b * vector.x + self.d * vector.y)
+    }
+
+    /// Returns the transform as a 3x3 matrix that acts on homogeneous 2D column vectors.
+    pub fn to_matrix3(&self) -> Matrix3<f32> {
+        Matrix3::new(
+            self.a, self.b, 0.0,
+            self.c, self.d, 0.0,
+            self.e, self.f, 1.0,
+        )
+    }
+
+    /// Returns the transform as a 4x4 matrix that acts on homogeneous 3D column vectors and
+    /// leaves z unchanged, like the model matrices of 3D APIs.
+    pub fn to_matrix4(&self) -> Matrix4<f32> {
+        Matrix4::new(
+            self.a, self.b, 0.0, 0.0,
+            self.c, self.d, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            self.e, self.f, 0.0, 1.0,
+        )
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+/// Composes transforms like matrices: `a * b` applies `b` first and then `a`.
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        rhs.then(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Vector3};
+
+    fn assert_close(a: Transform, b: Transform) {
+        let (a, b) = ([a.a, a.b, a.c, a.d, a.e, a.f], [b.a, b.b, b.c, b.d, b.e, b.f]);
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!((x - y).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn samples() -> Vec<Transform> {
+        vec![
+            Transform::identity(),
+            Transform::from_translation(3.0, -2.0),
+            Transform::from_rot(Deg(30.0)),
+            Transform::from_nonuniform_scale(2.0, -0.5),
+            Transform::from_skew(Deg(20.0), Deg(-10.0)),
+            Transform::from_matrix(1.5, 0.25, -0.75, 2.0, 4.0, 1.0),
+        ]
+    }
+
+    #[test]
+    fn inverses_undo_transforms() {
+        for t in samples() {
+            let inverse = t.invert().unwrap();
+            assert_close(t.then(inverse), Transform::identity());
+            assert_close(inverse.then(t), Transform::identity());
+        }
+    }
+
+    #[test]
+    fn singular_transforms_have_no_inverse() {
+        assert_eq!(Transform::from_scale(0.0).invert(), None);
+        assert_eq!(Transform::from_matrix(1.0, 2.0, 2.0, 4.0, 5.0, 6.0).invert(), None);
+        assert_eq!(Transform::from_matrix(f32::NAN, 0.0, 0.0, 1.0, 0.0, 0.0).invert(), None);
+    }
+
+    #[test]
+    fn multiplying_applies_the_right_hand_side_first() {
+        for a in samples() {
+            for b in samples() {
+                assert_close(a * b, b.then(a));
             }
         }
+        // rotating and then moving keeps the rotation around the shape's own origin
+        let t = Transform::from_rot(Deg(90.0)).then(Transform::from_translation(5.0, 0.0));
+        let p = t.transform_point(Point2::new(1.0, 0.0));
+        assert!((p.x - 5.0).abs() < 1e-5 && (p.y - 1.0).abs() < 1e-5, "{:?}", p);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn transforming_points_matches_the_matrix() {
+        let p = Point2::new(1.5, -2.0);
+        for t in samples() {
+            let mapped = t.transform_point(p);
+            let expected = t.to_matrix3() * Vector3::new(p.x, p.y, 1.0);
+            assert!((mapped.x - expected.x).abs() < 1e-5 && (mapped.y - expected.y).abs() < 1e-5);
+            let vector = t.transform_vector(Vector2::new(p.x, p.y));
+            let origin = t.transform_point(Point2::new(0.0, 0.0));
+            assert!((vector.x - (mapped.x - origin.x)).abs() < 1e-5);
+            assert!((vector.y - (mapped.y - origin.y)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn skews_lean_squares() {
+        let t = Transform::from_skew(Deg(45.0), Deg(0.0));
+        let p = t.transform_point(Point2::new(0.0, 1.0));
+        assert!((p.x - 1.0).abs() < 1e-5 && (p.y - 1.0).abs() < 1e-5, "{:?}", p);
+        assert_eq!(t.transform_point(Point2::new(1.0, 0.0)), Point2::new(1.0, 0.0));
+    }
+}
@@ -41,6 +41,9 @@ impl Host for Window {
 /// Spawns a new thread and passes in a `Window` instance representing the `Host` object on the 
 /// parent thread. The newly created window will listen for drawing commands on the main thread. 
 /// The `Window` instance can be used to obtain the sender.
+///
+/// The window shows drawing as soon as it arrives. Once the canvas calls `Canvas::present`, it
+/// only shows complete frames instead; see `easy_canvas::PresentMode`.
 pub fn create(title: &str, width: i32, height: i32, refresh_rate: f64, cb: fn(Window)) {
     let window = create_raw(title, width, height);
     unsafe {
@@ -84,6 +87,9 @@ fn create_raw(title: &str, width: i32, height: i32) -> *mut GLFWwindow {
         let w = glfwCreateWindow(width, height, title_c_str.as_ptr() as *const _,
             ptr::null_mut(), ptr::null_mut());
         glfwMakeContextCurrent(w);
+        // wait for vertical sync when swapping, so frames are never shown halfway through a
+        // screen refresh
+        glfwSwapInterval(1);
         gl::load_with(|s| {
             let c_str = CString::new(s).unwrap();
             glfwGetProcAddress(c_str.as_ptr() as _) as _